@@ -0,0 +1,138 @@
+//! Shared pieces of the benchmarks: the programs they run and a small
+//! timer that reports in the same format as `cargo bench` on nightly, so
+//! runs can be compared with tools like `cargo benchcmp`.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+pub const FIB: &str = include_str!("../programs/fib.love");
+pub const STRINGS: &str = include_str!("../programs/strings.love");
+pub const LOOP: &str = include_str!("../programs/loop.love");
+
+/// How long each sample should run for, at least.
+const SAMPLE_TIME: Duration = Duration::from_millis(20);
+const SAMPLES: usize = 25;
+
+/// A large love story made of `devotions` copies of a template, for
+/// lexing and parsing throughput.
+pub fn generated_source(devotions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..devotions {
+        source.push_str(&format!(
+            r#"// Devotion number {i}
+devotion feel{i}(a: number, b: number) -> number {{
+    heart sum{i} match a cuddle b kiss {i};
+    crush (sum{i} admires 100 and not (a soulmate b)) {{
+        whisper "so much love" cuddle "!";
+    }} butterflies {{
+        sum{i} match sum{i} breakup 1;
+    }}
+    dating (sum{i} envies 10) {{
+        sum{i} match sum{i} cuddle 1;
+    }}
+    promise sum{i};
+}}
+forever LIMIT{i} match feel{i}({i}, 2) split 3;
+"#
+        ));
+    }
+    source
+}
+
+/// Runs the benchmarks whose names contain the first command-line filter,
+/// or all of them.
+pub struct Bencher {
+    filter: Option<String>,
+}
+
+impl Bencher {
+    pub fn from_args() -> Self {
+        // `cargo bench` passes `--bench`; anything else is a name filter
+        let filter = env::args().skip(1).find(|arg| !arg.starts_with('-'));
+        Bencher { filter }
+    }
+
+    /// Times `f`, printing nanoseconds per iteration and, when `bytes` is
+    /// given, throughput.
+    pub fn bench<T>(&self, name: &str, bytes: Option<usize>, mut f: impl FnMut() -> T) {
+        self.measure(name, bytes, |iterations| {
+            let start = Instant::now();
+            for _ in 0..iterations {
+                black_box(f());
+            }
+            start.elapsed()
+        });
+    }
+
+    /// Like `bench`, but runs `setup` before each iteration to make what
+    /// `routine` consumes, and only times `routine`.
+    pub fn bench_with_setup<S, T>(
+        &self,
+        name: &str,
+        bytes: Option<usize>,
+        mut setup: impl FnMut() -> S,
+        mut routine: impl FnMut(S) -> T,
+    ) {
+        self.measure(name, bytes, |iterations| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..iterations {
+                let input = setup();
+                let start = Instant::now();
+                let output = black_box(routine(black_box(input)));
+                elapsed += start.elapsed();
+                // Dropping what the routine made isn't part of it either
+                drop(output);
+            }
+            elapsed
+        });
+    }
+
+    /// Samples with `run`, which runs a number of iterations and returns
+    /// how long they took, and prints the result.
+    fn measure(&self, name: &str, bytes: Option<usize>, mut run: impl FnMut(u64) -> Duration) {
+        if self.filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {
+            return;
+        }
+
+        // Find how many iterations fill a sample
+        let mut iterations = 1u64;
+        while run(iterations) < SAMPLE_TIME && iterations < 1 << 30 {
+            iterations *= 2;
+        }
+
+        let mut samples: Vec<f64> = (0..SAMPLES)
+            .map(|_| run(iterations).as_nanos() as f64 / iterations as f64)
+            .collect();
+        samples.sort_by(f64::total_cmp);
+
+        // Median, and the spread of the middle half like libtest's
+        // winsorized deviation
+        let median = samples[SAMPLES / 2];
+        let spread = samples[SAMPLES * 3 / 4] - samples[SAMPLES / 4];
+        let throughput = bytes
+            .map(|bytes| format!(" = {} MB/s", (bytes as f64 * 1000.0 / median) as u64))
+            .unwrap_or_default();
+        println!(
+            "test {:<28} ... bench: {:>13} ns/iter (+/- {}){}",
+            name,
+            group_digits(median as u64),
+            group_digits(spread as u64),
+            throughput,
+        );
+    }
+}
+
+fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
@@ -0,0 +1,44 @@
+//! Running love stories on the tree-walking interpreter and on the VM,
+//! not counting lexing, parsing, or making and dropping the engine. Run
+//! with `cargo bench --bench interpreter`.
+
+mod common;
+
+use common::{Bencher, FIB, LOOP, STRINGS};
+use love_language::interpreter::{Interpreter, SharedBuffer};
+use love_language::lexer::Lexer;
+use love_language::parser::ast::Ast;
+use love_language::parser::Parser;
+use love_language::vm::Vm;
+
+fn parse(source: &str) -> Ast {
+    let tokens = Lexer::new(source).tokenize().expect("benchmark source lexes");
+    Parser::new(tokens).parse().expect("benchmark source parses")
+}
+
+fn main() {
+    let bencher = Bencher::from_args();
+
+    for (name, source) in [("fib_20", FIB), ("strings_2000", STRINGS), ("loop_20000", LOOP)] {
+        let ast = parse(source);
+        bencher.bench_with_setup(
+            &format!("interpret_{}", name),
+            None,
+            || (Interpreter::with_output(Box::new(SharedBuffer::new())), ast.clone()),
+            // Handing the interpreter back keeps dropping it out of the timing
+            |(mut interpreter, ast)| {
+                let value = interpreter.interpret(ast).expect("benchmark source runs");
+                (interpreter, value)
+            },
+        );
+        bencher.bench_with_setup(
+            &format!("vm_{}", name),
+            None,
+            || (Vm::with_output(Box::new(SharedBuffer::new())), ast.clone()),
+            |(mut vm, ast)| {
+                let value = vm.interpret(ast).expect("benchmark source runs");
+                (vm, value)
+            },
+        );
+    }
+}
@@ -0,0 +1,15 @@
+//! Lexing throughput. Run with `cargo bench --bench lexer`.
+
+mod common;
+
+use common::{generated_source, Bencher, FIB, STRINGS};
+use love_language::lexer::Lexer;
+
+fn main() {
+    let bencher = Bencher::from_args();
+    let large = generated_source(500);
+
+    for (name, source) in [("lex_fib", FIB), ("lex_strings", STRINGS), ("lex_generated_500", large.as_str())] {
+        bencher.bench(name, Some(source.len()), || Lexer::new(source).tokenize().expect("benchmark source lexes"));
+    }
+}
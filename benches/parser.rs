@@ -0,0 +1,20 @@
+//! Parsing throughput, not counting lexing. Run with
+//! `cargo bench --bench parser`.
+
+mod common;
+
+use common::{generated_source, Bencher, FIB, STRINGS};
+use love_language::lexer::Lexer;
+use love_language::parser::Parser;
+
+fn main() {
+    let bencher = Bencher::from_args();
+    let large = generated_source(500);
+
+    for (name, source) in [("parse_fib", FIB), ("parse_strings", STRINGS), ("parse_generated_500", large.as_str())] {
+        let tokens = Lexer::new(source).tokenize().expect("benchmark source lexes");
+        bencher.bench(name, Some(source.len()), || {
+            Parser::new(tokens.clone()).parse().expect("benchmark source parses")
+        });
+    }
+}
@@ -0,0 +1,19 @@
+//! `love-lsp`, the Love Language server. Editors start it and talk to it
+//! over stdin and stdout with the Language Server Protocol.
+
+use std::io;
+use std::process::ExitCode;
+
+use love_language::lsp;
+
+fn main() -> ExitCode {
+    match lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+        Ok(true) => ExitCode::SUCCESS,
+        // The client exited without shutting the server down first
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("love-lsp: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::env;
+
+use crate::error::LoveError;
+use crate::shared_types::{Type, Value};
+
+/// What built-in devotions can see of the world outside the story.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// The arguments given to the story, e.g. those after its path on the
+    /// `love` command line.
+    pub args: Vec<String>,
+    /// Whether `secret(name)` may read environment variables. Off unless
+    /// the host turns it on, since the environment often holds credentials
+    /// an untrusted story shouldn't see.
+    pub allow_env: bool,
+}
+
+/// Devotions every love story can call without declaring them. Both engines
+/// define them as globals, so a story may still declare its own devotion
+/// with the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `goodbye(status)` ends the story right away, with `status` as the
+    /// exit status of the `love` process.
+    Goodbye,
+    /// `letters()` is how many arguments the story was given.
+    Letters,
+    /// `letter(n)` is the story's argument at index `n`, counting from 0,
+    /// or `lonely` if there are fewer.
+    Letter,
+    /// `secret(name)` is the value of the environment variable `name`, or
+    /// `lonely` if it isn't set. Fails unless the host allows reading the
+    /// environment.
+    Secret,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 4] = [Builtin::Goodbye, Builtin::Letters, Builtin::Letter, Builtin::Secret];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Goodbye => "goodbye",
+            Builtin::Letters => "letters",
+            Builtin::Letter => "letter",
+            Builtin::Secret => "secret",
+        }
+    }
+
+    /// The parameters the devotion takes, with their types.
+    pub fn params(self) -> &'static [(&'static str, Type)] {
+        match self {
+            Builtin::Goodbye => &[("status", Type::Number)],
+            Builtin::Letters => &[],
+            Builtin::Letter => &[("n", Type::Number)],
+            Builtin::Secret => &[("name", Type::Text)],
+        }
+    }
+
+    /// The type of what the devotion gives back, when it is always the
+    /// same.
+    pub fn returns(self) -> Option<Type> {
+        match self {
+            Builtin::Letters => Some(Type::Number),
+            Builtin::Goodbye | Builtin::Letter | Builtin::Secret => None,
+        }
+    }
+
+    /// Calls the devotion. The caller has already checked the number of
+    /// arguments.
+    pub fn call(self, arguments: Vec<Value>, context: &Context) -> Result<Value, LoveError> {
+        match self {
+            Builtin::Goodbye => match number(&arguments[0])? {
+                n if n.fract() == 0.0 && (0.0..=255.0).contains(&n) => Err(LoveError::Goodbye { status: n as u8 }),
+                n => Err(LoveError::runtime(format!("goodbye needs a whole number from 0 to 255, not {}", n))),
+            },
+            Builtin::Letters => Ok(Value::Number(context.args.len() as f64)),
+            Builtin::Letter => match number(&arguments[0])? {
+                n if n.fract() == 0.0 && n >= 0.0 => {
+                    Ok(context.args.get(n as usize).map_or(Value::Null, |arg| Value::Text(arg.clone())))
+                }
+                n => Err(LoveError::runtime(format!("letter needs a whole number of 0 or more, not {}", n))),
+            },
+            Builtin::Secret => match &arguments[0] {
+                Value::Text(_) if !context.allow_env => {
+                    Err(LoveError::runtime("secret can't read the environment here, it hasn't been allowed"))
+                }
+                Value::Text(name) => Ok(env::var(name).map_or(Value::Null, Value::Text)),
+                other => Err(LoveError::TypeMismatch { expected: Type::Text, found: other.get_type(), span: None }),
+            },
+        }
+    }
+}
+
+/// The number in `value`, which must be one.
+fn number(value: &Value) -> Result<f64, LoveError> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        other => Err(LoveError::TypeMismatch { expected: Type::Number, found: other.get_type(), span: None }),
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::builtins::Builtin;
+use crate::error::LoveError;
+use crate::optimizer::collect_assigned;
+use crate::parser::ast::Ast;
+use crate::scopes::Scopes;
+use crate::shared_types::{BinaryOp, Type};
+use crate::span::Span;
+
+/// What the checker knows about a name.
+#[derive(Clone)]
+enum Binding {
+    /// A variable, with its type when it can only ever hold one.
+    Value(Option<Type>),
+    /// A devotion that is never rebound, so every call goes to it.
+    Devotion { params: Vec<Type>, returns: Option<Type> },
+}
+
+/// Looks for type errors without running the love story: operators used
+/// on the wrong kinds of values, conditions that aren't feelings, devotion
+/// calls with the wrong number or types of arguments, and promises that
+/// break a devotion's declared return type.
+///
+/// Only mistakes that are certain are reported. A name that may hold
+/// different kinds of values over time is simply not checked.
+pub fn check(ast: &Ast) -> Result<(), LoveError> {
+    let mut errors = Checker::run(ast).errors;
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(LoveError::Multiple { errors }),
+    }
+}
+
+/// The type each `heart` and `forever` in the love story certainly has,
+/// keyed by where its declaration starts. Names that may hold different
+/// kinds of values over time are left out, as they are by `check`.
+pub fn declared_types(ast: &Ast) -> HashMap<usize, Type> {
+    Checker::run(ast).types
+}
+
+struct Checker {
+    assigned: HashSet<String>,
+    scopes: Scopes<Binding>,
+    /// Declared return types of the devotions being checked, innermost last.
+    returns: Vec<Option<Type>>,
+    errors: Vec<LoveError>,
+    /// Certain types of declarations, for `declared_types`.
+    types: HashMap<usize, Type>,
+}
+
+impl Checker {
+    /// Checks `ast` with the built-in devotions declared.
+    fn run(ast: &Ast) -> Checker {
+        let mut assigned = HashSet::new();
+        collect_assigned(ast, &mut assigned);
+        let mut checker = Checker {
+            assigned,
+            scopes: Scopes::new(),
+            returns: Vec::new(),
+            errors: Vec::new(),
+            types: HashMap::new(),
+        };
+        for builtin in Builtin::ALL {
+            let params = builtin.params().iter().map(|(_, t)| t.clone()).collect();
+            checker.declare(builtin.name(), Binding::Devotion { params, returns: builtin.returns() });
+        }
+        checker.statement(ast);
+        checker
+    }
+
+    fn declare(&mut self, name: &str, binding: Binding) {
+        // A name that is matched to new values later can't be trusted
+        let binding = if self.assigned.contains(name) { Binding::Value(None) } else { binding };
+        self.scopes.declare(name, binding);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.get(name)
+    }
+
+    fn mismatch(&mut self, expected: Type, found: Type, span: Span) {
+        self.errors.push(LoveError::TypeMismatch { expected, found, span: Some(span) });
+    }
+
+    fn statements(&mut self, statements: &[Ast]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Program { statements, .. } => self.statements(statements),
+            Ast::Block { statements, .. } => {
+                self.scopes.begin_block();
+                self.statements(statements);
+                self.scopes.end_block();
+            }
+            Ast::VariableDecl { name, initializer, span, .. } => {
+                let found = self.expression(initializer);
+                if let Some(found) = found.clone().filter(|_| !self.assigned.contains(name)) {
+                    self.types.insert(span.start, found);
+                }
+                self.declare(name, Binding::Value(found));
+            }
+            Ast::FunctionDecl { name, params, return_type, body, .. } => {
+                let signature = Binding::Devotion {
+                    params: params.iter().map(|(_, t, _)| t.clone()).collect(),
+                    returns: return_type.clone(),
+                };
+                self.declare(name, signature);
+
+                self.scopes.begin_devotion(HashMap::new());
+                for (param, param_type, _) in params {
+                    self.declare(param, Binding::Value(Some(param_type.clone())));
+                }
+                self.returns.push(return_type.clone());
+                self.statements(body);
+                self.returns.pop();
+                self.scopes.end_devotion();
+            }
+            Ast::If { condition, then_branch, else_branch, .. } => {
+                self.condition(condition);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Ast::While { condition, body, .. } => {
+                self.condition(condition);
+                self.statements(body);
+            }
+            Ast::ReturnStmt { value, span } => {
+                let found = match value {
+                    Some(value) => self.expression(value),
+                    None => Some(Type::Null),
+                };
+                if let (Some(Some(expected)), Some(found)) = (self.returns.last().cloned(), found) {
+                    if expected != found {
+                        let span = value.as_ref().map_or(*span, |v| v.span());
+                        self.mismatch(expected, found, span);
+                    }
+                }
+            }
+            Ast::ExpressionStmt { expr, .. } | Ast::PrintStmt { value: expr, .. } => {
+                self.expression(expr);
+            }
+            _ => {
+                self.expression(ast);
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Ast) {
+        if let Some(found) = self.expression(condition) {
+            if found != Type::Boolean {
+                self.errors.push(LoveError::NonFeelingCondition { found, span: Some(condition.span()) });
+            }
+        }
+    }
+
+    /// Checks an expression, returning its type when it is certain.
+    fn expression(&mut self, ast: &Ast) -> Option<Type> {
+        match ast {
+            Ast::Literal { value, .. } => Some(value.get_type()),
+            Ast::Variable { name, .. } => match self.lookup(name)? {
+                Binding::Value(found) => found.clone(),
+                Binding::Devotion { .. } => Some(Type::Function),
+            },
+            Ast::Grouping { expr, .. } => self.expression(expr),
+            Ast::Assign { value, .. } => self.expression(value),
+            Ast::Call { callee, arguments, span, .. } => {
+                let found: Vec<Option<Type>> = arguments.iter().map(|a| self.expression(a)).collect();
+                match self.lookup(callee).cloned() {
+                    Some(Binding::Devotion { params, returns }) => {
+                        if params.len() != arguments.len() {
+                            self.errors.push(LoveError::ArityMismatch {
+                                name: callee.clone(),
+                                expected: params.len(),
+                                got: arguments.len(),
+                                span: Some(*span),
+                            });
+                            return returns;
+                        }
+                        for ((expected, found), argument) in params.into_iter().zip(found).zip(arguments) {
+                            match found {
+                                Some(found) if found != expected => self.mismatch(expected, found, argument.span()),
+                                _ => {}
+                            }
+                        }
+                        returns
+                    }
+                    Some(Binding::Value(Some(found))) if found != Type::Function => {
+                        self.errors.push(LoveError::NotCallable { name: callee.clone(), span: Some(*span) });
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            Ast::Unary { operator, operand, span } => {
+                let found = self.expression(operand);
+                if *operator == BinaryOp::Not {
+                    self.expect(Type::Boolean, found, *span);
+                }
+                Some(Type::Boolean)
+            }
+            Ast::Binary { left, operator, right, span } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                self.binary(left, operator, right, *span)
+            }
+            // Statements only show up here when nested where an expression
+            // was expected, which the parser doesn't produce
+            _ => {
+                self.statement(ast);
+                None
+            }
+        }
+    }
+
+    /// Reports `found` if it is known and isn't `expected`.
+    fn expect(&mut self, expected: Type, found: Option<Type>, span: Span) -> bool {
+        match found {
+            Some(found) if found != expected => {
+                self.mismatch(expected, found, span);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Mirrors `operators::binary`: checks the operands the way it would
+    /// and returns the type of the result.
+    fn binary(&mut self, left: Option<Type>, operator: &BinaryOp, right: Option<Type>, span: Span) -> Option<Type> {
+        match operator {
+            BinaryOp::Add if left == Some(Type::Text) || right == Some(Type::Text) => Some(Type::Text),
+            // Either side might still turn out to be text
+            BinaryOp::Add if left.is_none() || right.is_none() => None,
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide => {
+                let _ = self.expect(Type::Number, left, span) && self.expect(Type::Number, right, span);
+                Some(Type::Number)
+            }
+            BinaryOp::Greater | BinaryOp::Less | BinaryOp::GreaterEqual | BinaryOp::LessEqual => {
+                let _ = self.expect(Type::Number, left, span) && self.expect(Type::Number, right, span);
+                Some(Type::Boolean)
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                let _ = self.expect(Type::Boolean, left, span) && self.expect(Type::Boolean, right, span);
+                Some(Type::Boolean)
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Not => Some(Type::Boolean),
+        }
+    }
+}
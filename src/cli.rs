@@ -0,0 +1,387 @@
+use std::path::PathBuf;
+
+use love_language::linter::{LintConfig, Rule, Severity};
+use love_language::runner::{Engine, Source};
+
+pub const USAGE: &str = "\
+love - the Love Language 💘
+
+Usage:
+    love [run options] <file.love> [args...]
+    love [run options] -e <code> [args...]
+    love <command> [options] ...
+
+Wherever a file is expected, '-' reads the love story from stdin and
+'-e <code>' takes it from the command line.
+
+Commands:
+    run        Run a love story
+    check      Parse and type-check a love story without running it
+    repl       Start an interactive session (the default with no arguments)
+    tokens     Print the tokens of a love story
+    ast        Print the syntax tree of a love story
+    fmt        Print a love story in the canonical style
+    lint       Look for likely mistakes in a love story
+    highlight  Print the class of every token of a love story as JSON
+
+Run 'love <command> --help' for more about a command.
+
+Setting LOVE_SEED to a number works like '--seed'.";
+
+const RUN_USAGE: &str = "\
+love run - run a love story
+
+Usage: love run [options] <file.love | - | -e <code>> [args...]
+
+Everything after the story is passed to the script, apart from a '--'
+right after it, which can separate the story from arguments that look like
+options.
+
+Options:
+    -e <code>       Run <code> instead of a file
+    --vm            Run on the bytecode VM instead of the tree-walker
+    -O, --optimize  Fold constants and drop dead branches before running
+    -q, --plain     Print only the story's output and errors, without color
+    --no-plain      Print banners, emojis and colors even when piped
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+const CHECK_USAGE: &str = "\
+love check - parse and type-check a love story without running it
+
+Usage: love check <file.love | - | -e <code>>
+
+Options:
+    -e <code>       Check <code> instead of a file
+    -q, --plain     Print only the story's output and errors, without color
+    --no-plain      Print banners, emojis and colors even when piped
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+const REPL_USAGE: &str = "\
+love repl - start an interactive session
+
+Usage: love repl [options]
+
+Options:
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+const TOKENS_USAGE: &str = "\
+love tokens - print the tokens of a love story, one per line
+
+Usage: love tokens <file.love | - | -e <code>>
+
+Options:
+    -e <code>       Print the tokens of <code> instead of a file
+    -q, --plain     Print only the story's output and errors, without color
+    --no-plain      Print banners, emojis and colors even when piped
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+const AST_USAGE: &str = "\
+love ast - print the syntax tree of a love story
+
+Usage: love ast [options] <file.love | - | -e <code>>
+
+Options:
+    -e <code>       Print the tree of <code> instead of a file
+    -O, --optimize  Print the tree after optimizing it
+    -q, --plain     Print only the story's output and errors, without color
+    --no-plain      Print banners, emojis and colors even when piped
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+const FMT_USAGE: &str = "\
+love fmt - print a love story in the canonical style
+
+Usage: love fmt [options] <file.love | - | -e <code>>
+
+Options:
+    --check         Only check the formatting, exiting with 7 if it's off
+    -e <code>       Format <code> instead of a file
+    -q, --plain     Print only the story's output and errors, without color
+    --no-plain      Print banners, emojis and colors even when piped
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+const LINT_USAGE: &str = "\
+love lint - look for likely mistakes in a love story
+
+Usage: love lint [options] <file.love | - | -e <code>>
+
+Exits with 7 if any denied lint is found.
+
+Options:
+    -A, --allow <rule>  Don't report <rule>
+    -W, --warn <rule>   Report <rule> as a warning
+    -D, --deny <rule>   Report <rule> as an error, failing the lint
+    -e <code>           Lint <code> instead of a file
+    -q, --plain         Print only the story's output and errors, without color
+    --no-plain          Print banners, emojis and colors even when piped
+    --seed <n>          Pick the same flavor text every time for the same <n>
+    --no-flavor         Leave out flavor text
+    -h, --help          Print this help
+
+Rules ('all' names every one of them):
+    unused-heart    A heart or forever that is never read (warn)
+    after-promise   Statements after a promise, which never run (warn)
+    always-yes      A crush or dating condition that is always yes (warn)
+    shadowed-vow    A heart that hides a devotion's parameter (warn)
+    quiet-forever   A forever whose name isn't in capitals (warn)
+    stuck-dating    A dating loop that never changes its condition (deny)";
+
+const HIGHLIGHT_USAGE: &str = "\
+love highlight - print the class of every token of a love story as JSON
+
+Usage: love highlight <file.love | - | -e <code>>
+
+Prints an array with an object per token or comment: its class (keyword,
+operator, type, literal, identifier, function, comment or punctuation),
+text, line, column, and start and end byte offsets.
+
+Options:
+    -e <code>       Classify <code> instead of a file
+    -q, --plain     Print only the story's output and errors, without color
+    --no-plain      Print banners, emojis and colors even when piped
+    --seed <n>      Pick the same flavor text every time for the same <n>
+    --no-flavor     Leave out flavor text
+    -h, --help      Print this help";
+
+/// What the command line asked for.
+pub enum Command {
+    Run { source: Source, engine: Engine, optimize: bool, args: Vec<String> },
+    Check { source: Source },
+    Repl,
+    Tokens { source: Source },
+    Ast { source: Source, optimize: bool },
+    Fmt { source: Source, check: bool },
+    Lint { source: Source, config: LintConfig },
+    Highlight { source: Source },
+    /// `--help` for the tool or one of its commands.
+    Help(&'static str),
+}
+
+/// Switches that apply to whichever command runs.
+#[derive(Default)]
+pub struct Options {
+    /// Leave out banners, emojis and colors, or keep them with `Some(false)`.
+    /// When neither was asked for, output is plain unless stdout is a
+    /// terminal.
+    pub plain: Option<bool>,
+    /// Seed for picking flavor text, to make it reproducible.
+    pub seed: Option<u64>,
+    /// Leave out flavor text altogether.
+    pub no_flavor: bool,
+}
+
+/// A command line that doesn't make sense, with the usage text to show.
+pub struct UsageError {
+    pub message: String,
+    pub usage: &'static str,
+}
+
+/// Parses the arguments after the program name.
+///
+/// A bare file is shorthand for `run`, so `love story.love` keeps working,
+/// and no arguments at all start the REPL.
+pub fn parse(args: Vec<String>) -> Result<(Command, Options), UsageError> {
+    let mut options = Options::default();
+    let command = parse_command(args, &mut options)?;
+    Ok((command, options))
+}
+
+fn parse_command(args: Vec<String>, options: &mut Options) -> Result<Command, UsageError> {
+    let mut args = args.into_iter();
+    let Some(first) = args.next() else {
+        return Ok(Command::Repl);
+    };
+    let rest: Vec<String> = args.collect();
+    match first.as_str() {
+        "-h" | "--help" | "help" => Ok(Command::Help(USAGE)),
+        "run" => parse_run(rest, options),
+        "check" => single_file(rest, options, CHECK_USAGE, &[], |source, _| Command::Check { source }),
+        "tokens" => single_file(rest, options, TOKENS_USAGE, &[], |source, _| Command::Tokens { source }),
+        "ast" => single_file(rest, options, AST_USAGE, &["-O", "--optimize"], |source, optimize| Command::Ast { source, optimize }),
+        "fmt" => single_file(rest, options, FMT_USAGE, &["--check"], |source, check| Command::Fmt { source, check }),
+        "lint" => parse_lint(rest, options),
+        "highlight" => single_file(rest, options, HIGHLIGHT_USAGE, &[], |source, _| Command::Highlight { source }),
+        "repl" => {
+            let mut rest = rest.into_iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "-h" | "--help" => return Ok(Command::Help(REPL_USAGE)),
+                    _ if flavor_option(&arg, &mut rest, options, REPL_USAGE)? => {}
+                    _ => return Err(unexpected(&arg, REPL_USAGE)),
+                }
+            }
+            Ok(Command::Repl)
+        }
+        _ => {
+            let mut all = vec![first];
+            all.extend(rest);
+            parse_run(all, options).map_err(|e| UsageError { usage: USAGE, ..e })
+        }
+    }
+}
+
+/// `[options] <story> [args...]` for running a story.
+fn parse_run(args: Vec<String>, options: &mut Options) -> Result<Command, UsageError> {
+    let mut engine = Engine::default();
+    let mut optimize = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let source = match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(RUN_USAGE)),
+            "--vm" => {
+                engine = Engine::Vm;
+                continue;
+            }
+            "-O" | "--optimize" => {
+                optimize = true;
+                continue;
+            }
+            "-q" | "--quiet" | "--plain" => {
+                options.plain = Some(true);
+                continue;
+            }
+            "--no-plain" => {
+                options.plain = Some(false);
+                continue;
+            }
+            _ if flavor_option(&arg, &mut args, options, RUN_USAGE)? => continue,
+            "-e" => Source::Inline(args.next().ok_or_else(|| missing_code(RUN_USAGE))?),
+            "--" => file(args.next().ok_or_else(|| missing_file(RUN_USAGE))?),
+            "-" => Source::Stdin,
+            option if option.starts_with('-') => return Err(unexpected(option, RUN_USAGE)),
+            _ => file(arg),
+        };
+        // A '--' right after the story only marks where its arguments start
+        let mut args: Vec<String> = args.collect();
+        if args.first().is_some_and(|arg| arg == "--") {
+            args.remove(0);
+        }
+        return Ok(Command::Run { source, engine, optimize, args });
+    }
+    Err(missing_file(RUN_USAGE))
+}
+
+/// `[options] <file>` for the commands that look at a story without
+/// running it. `command` builds the result from the story and whether
+/// `switch`, the spellings of the command's own on/off option if it has
+/// one, was given.
+fn single_file(
+    args: Vec<String>,
+    options: &mut Options,
+    usage: &'static str,
+    switch: &[&str],
+    command: impl FnOnce(Source, bool) -> Command,
+) -> Result<Command, UsageError> {
+    let mut source = None;
+    let mut switched = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let found = match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(usage)),
+            _ if switch.contains(&arg.as_str()) => {
+                switched = true;
+                continue;
+            }
+            "-q" | "--quiet" | "--plain" => {
+                options.plain = Some(true);
+                continue;
+            }
+            "--no-plain" => {
+                options.plain = Some(false);
+                continue;
+            }
+            _ if flavor_option(&arg, &mut args, options, usage)? => continue,
+            "-e" => Source::Inline(args.next().ok_or_else(|| missing_code(usage))?),
+            "-" => Source::Stdin,
+            option if option.starts_with('-') => return Err(unexpected(option, usage)),
+            _ => file(arg.clone()),
+        };
+        if source.replace(found).is_some() {
+            return Err(unexpected(&arg, usage));
+        }
+    }
+    let source = source.ok_or_else(|| missing_file(usage))?;
+    Ok(command(source, switched))
+}
+
+/// `[options] <file>` for `lint`, which also takes the severity of each
+/// rule.
+fn parse_lint(args: Vec<String>, options: &mut Options) -> Result<Command, UsageError> {
+    let mut config = LintConfig::new();
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let severity = match arg.as_str() {
+            "-A" | "--allow" => Severity::Allow,
+            "-W" | "--warn" => Severity::Warn,
+            "-D" | "--deny" => Severity::Deny,
+            _ => {
+                // Inline code is the story, even if it looks like an option
+                let inline = arg == "-e";
+                rest.push(arg);
+                if inline {
+                    rest.extend(args.next());
+                }
+                continue;
+            }
+        };
+        let rule = args.next().ok_or_else(|| UsageError {
+            message: format!("{} needs a rule", arg),
+            usage: LINT_USAGE,
+        })?;
+        match Rule::from_name(&rule) {
+            Some(rule) => config.set(rule, severity),
+            None if rule == "all" => config.set_all(severity),
+            None => return Err(UsageError { message: format!("there is no lint rule called '{}'", rule), usage: LINT_USAGE }),
+        }
+    }
+    single_file(rest, options, LINT_USAGE, &[], |source, _| Command::Lint { source, config })
+}
+
+/// Handles `--seed <n>` and `--no-flavor`, which every command takes.
+/// Returns whether `arg` was one of them.
+fn flavor_option(
+    arg: &str,
+    args: &mut impl Iterator<Item = String>,
+    options: &mut Options,
+    usage: &'static str,
+) -> Result<bool, UsageError> {
+    match arg {
+        "--seed" => {
+            let seed = args.next()
+                .and_then(|seed| seed.parse().ok())
+                .ok_or_else(|| UsageError { message: "--seed needs a whole number".to_string(), usage })?;
+            options.seed = Some(seed);
+        }
+        "--no-flavor" => options.no_flavor = true,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn file(path: String) -> Source {
+    Source::File(PathBuf::from(path))
+}
+
+fn unexpected(arg: &str, usage: &'static str) -> UsageError {
+    UsageError { message: format!("unexpected argument '{}'", arg), usage }
+}
+
+fn missing_code(usage: &'static str) -> UsageError {
+    UsageError { message: "-e needs the love story's code".to_string(), usage }
+}
+
+fn missing_file(usage: &'static str) -> UsageError {
+    UsageError { message: "missing the love story to read".to_string(), usage }
+}
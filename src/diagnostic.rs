@@ -0,0 +1,193 @@
+use colored::*;
+
+use crate::error::{LoveError, TraceFrame};
+use crate::span::Span;
+
+/// How many innermost and outermost love trace frames to show.
+const TRACE_HEAD: usize = 5;
+const TRACE_TAIL: usize = 3;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    /// The story can't run, or shouldn't be accepted as it is.
+    #[default]
+    Error,
+    /// Something that looks like a mistake, but doesn't stop the story.
+    Warning,
+}
+
+/// A renderable report about a problem in a love story: the message, where
+/// it happened, and what the author might do about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>,
+    pub hint: Option<String>,
+    pub trace: Vec<TraceFrame>,
+    pub footer: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Diagnostic {
+            level: Level::Error,
+            code: None,
+            message: message.into(),
+            span: None,
+            label: None,
+            hint: None,
+            trace: Vec::new(),
+            footer: None,
+        }
+    }
+
+    pub fn from_error(error: &LoveError) -> Self {
+        Diagnostic {
+            level: Level::Error,
+            code: Some(error.code()),
+            message: error.to_string(),
+            span: error.span(),
+            label: Some(error.label()),
+            hint: error.hint().map(str::to_string),
+            trace: error.trace().to_vec(),
+            footer: None,
+        }
+    }
+
+    /// Adds a line of love-themed flavor text below the report.
+    pub fn with_footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = Some(footer.into());
+        self
+    }
+
+    /// Renders the diagnostic `rustc`-style, quoting the offending line of
+    /// `source`. `origin` names the source (usually a file path).
+    pub fn render(&self, source: &str, origin: &str) -> String {
+        let (level, color) = match self.level {
+            Level::Error => ("error", Color::BrightRed),
+            Level::Warning => ("warning", Color::BrightYellow),
+        };
+        let heading = match self.code {
+            Some(code) => format!("{}[{}]", level, code),
+            None => level.to_string(),
+        };
+        let mut out = format!("{}: {}\n", heading.color(color).bold(), self.message.bold());
+
+        match self.span {
+            Some(span) => {
+                let (line_number, column, line) = locate(source, span);
+                let gutter = " ".repeat(line_number.to_string().len());
+                let pipe = "|".bright_blue().bold();
+                let underline_width = span_width(line, column, span);
+
+                out.push_str(&format!(
+                    "{}{} {}:{}:{}\n",
+                    gutter, "-->".bright_blue().bold(), origin, line_number, column
+                ));
+                out.push_str(&format!("{} {}\n", gutter, pipe));
+                out.push_str(&format!(
+                    "{} {} {}\n",
+                    line_number.to_string().bright_blue().bold(), pipe, line
+                ));
+                out.push_str(&format!(
+                    "{} {} {}{} {}\n",
+                    gutter,
+                    pipe,
+                    " ".repeat(column - 1),
+                    "^".repeat(underline_width).color(color).bold(),
+                    self.label.as_deref().unwrap_or("").color(color).bold()
+                ));
+                if let Some(hint) = &self.hint {
+                    out.push_str(&format!("{} {}\n", gutter, pipe));
+                    out.push_str(&format!("{} {} {}\n", gutter, "= hint:".bright_cyan().bold(), hint));
+                }
+            }
+            None => {
+                out.push_str(&format!("{} {}\n", "-->".bright_blue().bold(), origin));
+                if let Some(hint) = &self.hint {
+                    out.push_str(&format!("{} {}\n", "= hint:".bright_cyan().bold(), hint));
+                }
+            }
+        }
+
+        if !self.trace.is_empty() {
+            out.push_str(&format!("{}\n", "love trace (most recent call first):".bright_cyan().bold()));
+            for (depth, frame) in self.trace.iter().enumerate() {
+                // Runaway recursion produces hundreds of identical frames
+                if depth == TRACE_HEAD && self.trace.len() > TRACE_HEAD + TRACE_TAIL {
+                    let hidden = self.trace.len() - TRACE_HEAD - TRACE_TAIL;
+                    out.push_str(&format!("  ... {} more devotions ...\n", hidden));
+                }
+                if depth >= TRACE_HEAD && depth < self.trace.len().saturating_sub(TRACE_TAIL) {
+                    continue;
+                }
+                let (line_number, column, _) = locate(source, frame.call_site);
+                out.push_str(&format!(
+                    "  {}: in devotion '{}', called at {}:{}:{}\n",
+                    depth, frame.function.bright_yellow(), origin, line_number, column
+                ));
+            }
+        }
+
+        if let Some(footer) = &self.footer {
+            out.push('\n');
+            out.push_str(&footer.bright_magenta().to_string());
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+/// Renders `error` against `source`, with an optional flavor-text footer.
+/// Collected errors are rendered one after another with a closing summary.
+pub fn render_error(error: &LoveError, source: &str, origin: &str, footer: Option<String>) -> String {
+    let errors = error.errors();
+    let mut out = String::new();
+    for error in &errors {
+        out.push_str(&Diagnostic::from_error(error).render(source, origin));
+        out.push('\n');
+    }
+    if errors.len() > 1 {
+        out.push_str(&format!(
+            "{}\n",
+            format!("{} errors found in {}", errors.len(), origin).bright_red().bold()
+        ));
+    }
+    if let Some(footer) = footer {
+        out.push_str(&footer.bright_magenta().to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// Finds the 1-based line number and column of `span` in `source`, along
+/// with the text of that line. Columns are counted in characters.
+fn locate(source: &str, span: Span) -> (usize, usize, &str) {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line_number = source[..start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count() + 1;
+    (line_number, column, source[line_start..line_end].trim_end_matches('\r'))
+}
+
+/// How many carets to draw: the span's width in characters, clipped to the
+/// quoted line and never less than one.
+fn span_width(line: &str, column: usize, span: Span) -> usize {
+    let remaining: String = line.chars().skip(column - 1).collect();
+    let mut width = 0;
+    let mut bytes = 0;
+    for c in remaining.chars() {
+        if bytes >= span.len() {
+            break;
+        }
+        bytes += c.len_utf8();
+        width += 1;
+    }
+    width.max(1)
+}
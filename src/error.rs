@@ -1,16 +1,385 @@
+use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
+use crate::shared_types::Type;
+use crate::span::Span;
 
-#[derive(Error, Debug)]
+/// Which stage of running a love story an error comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Lexer,
+    Parser,
+    Resolver,
+    Type,
+    Runtime,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Lexer => write!(f, "Lexer error"),
+            ErrorKind::Parser => write!(f, "Parser error"),
+            ErrorKind::Resolver => write!(f, "Resolver error"),
+            ErrorKind::Type => write!(f, "Type error"),
+            ErrorKind::Runtime => write!(f, "Runtime error"),
+        }
+    }
+}
+
+impl ErrorKind {
+    /// The status the `love` command exits with when a love story fails
+    /// with an error of this kind. 2 is left for command-line mistakes.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Runtime => 1,
+            ErrorKind::Lexer => 3,
+            ErrorKind::Parser => 4,
+            ErrorKind::Resolver => 5,
+            ErrorKind::Type => 6,
+        }
+    }
+}
+
+/// One devotion call on the interpreter's call stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub call_site: Span,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum LoveError {
-    #[error("Lexer error: {0}")]
-    Lexer(String),
-    
-    #[error("Parser error: {0}")]
-    Parser(String),
-    
-    #[error("Runtime error: {0}")]
-    Runtime(String),
-
-    #[error("Type error: {0}")]
-    Type(String),
-}
\ No newline at end of file
+    // Lexer errors
+    #[error("Invalid token '{text}'")]
+    InvalidToken { text: String, span: Span },
+
+    // Parser errors
+    #[error("Expected {expected}{}", context.as_ref().map(|c| format!(" {}", c)).unwrap_or_default())]
+    Expected { expected: String, context: Option<String>, span: Span },
+
+    #[error("Invalid assignment target")]
+    InvalidAssignmentTarget { span: Span },
+
+    // Resolver errors
+    #[error("'{name}' is used before it is declared")]
+    UseBeforeDeclaration { name: String, span: Span },
+
+    #[error("'{name}' is already declared in this scope")]
+    DuplicateDeclaration { name: String, span: Span, previous: Span },
+
+    #[error("Devotion '{name}' is declared inside a block or another devotion")]
+    NestedDevotion { name: String, span: Span },
+
+    // Type errors
+    #[error("Expected {expected}, but found {found}")]
+    TypeMismatch { expected: Type, found: Type, span: Option<Span> },
+
+    #[error("Condition must evaluate to a feeling (yes/no), but found {found}")]
+    NonFeelingCondition { found: Type, span: Option<Span> },
+
+    // Runtime errors
+    #[error("Undefined variable '{name}'.")]
+    UndefinedVariable { name: String, span: Option<Span> },
+
+    #[error("Undefined function '{name}'")]
+    UndefinedFunction { name: String, span: Option<Span> },
+
+    #[error("'{name}' is not a function")]
+    NotCallable { name: String, span: Option<Span> },
+
+    #[error("'{name}' expected {expected} arguments but got {got}.")]
+    ArityMismatch { name: String, expected: usize, got: usize, span: Option<Span> },
+
+    #[error("Cannot split by zero!")]
+    DivideByZero { span: Option<Span> },
+
+    #[error("{operation} is not supported yet")]
+    Unsupported { operation: String, span: Option<Span> },
+
+    #[error("Only .love files can contain our love story! 💝")]
+    NotALoveFile { path: String },
+
+    #[error("Failed to {action}: {message}")]
+    Io { action: String, message: String, span: Option<Span> },
+
+    #[error("{message}")]
+    Runtime { message: String, span: Option<Span> },
+
+    #[error("Too much devotion! Calls nested deeper than {limit} devotions")]
+    CallDepthExceeded { limit: usize, span: Option<Span> },
+
+    #[error("This love story took more than {limit} steps")]
+    StepLimitExceeded { limit: u64, span: Option<Span> },
+
+    #[error("This love story ran longer than {limit:?}")]
+    TimeLimitExceeded { limit: Duration, span: Option<Span> },
+
+    #[error("This love story was cancelled")]
+    Cancelled { span: Option<Span> },
+
+    #[error("This love story tried to hold more than {limit} bytes")]
+    MemoryLimitExceeded { limit: usize, span: Option<Span> },
+
+    /// Not a mistake: the story called `goodbye(status)` to end itself.
+    #[error("This love story said goodbye with status {status}")]
+    Goodbye { status: u8 },
+
+    #[error("{} problems in this love story", errors.len())]
+    Multiple { errors: Vec<LoveError> },
+
+    /// An error raised inside devotions, with the calls that led to it,
+    /// innermost first.
+    #[error("{error}")]
+    Traced { error: Box<LoveError>, trace: Vec<TraceFrame> },
+}
+
+impl LoveError {
+    pub fn runtime(message: impl Into<String>) -> Self {
+        LoveError::Runtime { message: message.into(), span: None }
+    }
+
+    pub fn expected(expected: impl Into<String>, context: Option<&str>, span: Span) -> Self {
+        LoveError::Expected {
+            expected: expected.into(),
+            context: context.map(str::to_string),
+            span,
+        }
+    }
+
+    pub fn io(action: impl Into<String>, error: std::io::Error) -> Self {
+        LoveError::Io { action: action.into(), message: error.to_string(), span: None }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            LoveError::Multiple { errors } => errors.first().map_or(ErrorKind::Parser, LoveError::kind),
+            LoveError::Traced { error, .. } => error.kind(),
+            LoveError::InvalidToken { .. } => ErrorKind::Lexer,
+            LoveError::Expected { .. }
+            | LoveError::InvalidAssignmentTarget { .. } => ErrorKind::Parser,
+            LoveError::UseBeforeDeclaration { .. }
+            | LoveError::DuplicateDeclaration { .. }
+            | LoveError::NestedDevotion { .. } => ErrorKind::Resolver,
+            LoveError::TypeMismatch { .. }
+            | LoveError::NonFeelingCondition { .. } => ErrorKind::Type,
+            LoveError::UndefinedVariable { .. }
+            | LoveError::UndefinedFunction { .. }
+            | LoveError::NotCallable { .. }
+            | LoveError::ArityMismatch { .. }
+            | LoveError::DivideByZero { .. }
+            | LoveError::Unsupported { .. }
+            | LoveError::NotALoveFile { .. }
+            | LoveError::Io { .. }
+            | LoveError::Runtime { .. }
+            | LoveError::CallDepthExceeded { .. }
+            | LoveError::StepLimitExceeded { .. }
+            | LoveError::TimeLimitExceeded { .. }
+            | LoveError::Cancelled { .. }
+            | LoveError::MemoryLimitExceeded { .. }
+            | LoveError::Goodbye { .. } => ErrorKind::Runtime,
+        }
+    }
+
+    /// The status the `love` command exits with for this error: the one a
+    /// story asked for with `goodbye`, or otherwise one per kind of error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            LoveError::Traced { error, .. } => error.exit_code(),
+            LoveError::Goodbye { status } => *status,
+            other => other.kind().exit_code(),
+        }
+    }
+
+    /// A stable identifier for this kind of error. Codes never change
+    /// meaning once published, so tools can match on them.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoveError::Multiple { errors } => errors.first().map_or("E0200", LoveError::code),
+            LoveError::Traced { error, .. } => error.code(),
+            LoveError::InvalidToken { .. } => "E0101",
+            LoveError::Expected { .. } => "E0201",
+            LoveError::InvalidAssignmentTarget { .. } => "E0202",
+            LoveError::UseBeforeDeclaration { .. } => "E0501",
+            LoveError::DuplicateDeclaration { .. } => "E0502",
+            LoveError::NestedDevotion { .. } => "E0503",
+            LoveError::TypeMismatch { .. } => "E0301",
+            LoveError::NonFeelingCondition { .. } => "E0302",
+            LoveError::Runtime { .. } => "E0401",
+            LoveError::UndefinedVariable { .. } => "E0402",
+            LoveError::UndefinedFunction { .. } => "E0403",
+            LoveError::NotCallable { .. } => "E0404",
+            LoveError::ArityMismatch { .. } => "E0405",
+            LoveError::DivideByZero { .. } => "E0406",
+            LoveError::Unsupported { .. } => "E0407",
+            LoveError::NotALoveFile { .. } => "E0408",
+            LoveError::Io { .. } => "E0409",
+            LoveError::StepLimitExceeded { .. } => "E0410",
+            LoveError::TimeLimitExceeded { .. } => "E0411",
+            LoveError::Cancelled { .. } => "E0412",
+            LoveError::MemoryLimitExceeded { .. } => "E0413",
+            LoveError::Goodbye { .. } => "E0414",
+            LoveError::CallDepthExceeded { .. } => "E0415",
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            LoveError::Multiple { errors } => errors.first().and_then(LoveError::span),
+            LoveError::Traced { error, .. } => error.span(),
+            LoveError::InvalidToken { span, .. }
+            | LoveError::Expected { span, .. }
+            | LoveError::InvalidAssignmentTarget { span }
+            | LoveError::UseBeforeDeclaration { span, .. }
+            | LoveError::DuplicateDeclaration { span, .. }
+            | LoveError::NestedDevotion { span, .. } => Some(*span),
+            LoveError::TypeMismatch { span, .. }
+            | LoveError::NonFeelingCondition { span, .. }
+            | LoveError::UndefinedVariable { span, .. }
+            | LoveError::UndefinedFunction { span, .. }
+            | LoveError::NotCallable { span, .. }
+            | LoveError::ArityMismatch { span, .. }
+            | LoveError::DivideByZero { span }
+            | LoveError::Unsupported { span, .. }
+            | LoveError::Io { span, .. }
+            | LoveError::Runtime { span, .. }
+            | LoveError::CallDepthExceeded { span, .. }
+            | LoveError::StepLimitExceeded { span, .. }
+            | LoveError::TimeLimitExceeded { span, .. }
+            | LoveError::Cancelled { span }
+            | LoveError::MemoryLimitExceeded { span, .. } => *span,
+            LoveError::NotALoveFile { .. } | LoveError::Goodbye { .. } => None,
+        }
+    }
+
+    /// Attaches `span` to the error unless it already points somewhere
+    /// more precise.
+    pub fn with_span(mut self, new_span: Span) -> Self {
+        if let LoveError::Traced { error, trace } = self {
+            return LoveError::Traced { error: Box::new(error.with_span(new_span)), trace };
+        }
+        match &mut self {
+            LoveError::TypeMismatch { span, .. }
+            | LoveError::NonFeelingCondition { span, .. }
+            | LoveError::UndefinedVariable { span, .. }
+            | LoveError::UndefinedFunction { span, .. }
+            | LoveError::NotCallable { span, .. }
+            | LoveError::ArityMismatch { span, .. }
+            | LoveError::DivideByZero { span }
+            | LoveError::Unsupported { span, .. }
+            | LoveError::Io { span, .. }
+            | LoveError::Runtime { span, .. }
+            | LoveError::CallDepthExceeded { span, .. }
+            | LoveError::StepLimitExceeded { span, .. }
+            | LoveError::TimeLimitExceeded { span, .. }
+            | LoveError::Cancelled { span }
+            | LoveError::MemoryLimitExceeded { span, .. } => {
+                if span.is_none() {
+                    *span = Some(new_span);
+                }
+            }
+            LoveError::InvalidToken { .. }
+            | LoveError::Expected { .. }
+            | LoveError::InvalidAssignmentTarget { .. }
+            | LoveError::UseBeforeDeclaration { .. }
+            | LoveError::DuplicateDeclaration { .. }
+            | LoveError::NestedDevotion { .. }
+            | LoveError::NotALoveFile { .. }
+            | LoveError::Goodbye { .. }
+            | LoveError::Multiple { .. }
+            | LoveError::Traced { .. } => {}
+        }
+        self
+    }
+
+    /// The devotion calls that led to this error, innermost first. Empty
+    /// for errors raised outside any devotion.
+    pub fn trace(&self) -> &[TraceFrame] {
+        match self {
+            LoveError::Traced { trace, .. } => trace,
+            _ => &[],
+        }
+    }
+
+    /// The individual errors this error stands for: itself, or each error
+    /// collected in a `Multiple`.
+    pub fn errors(&self) -> Vec<&LoveError> {
+        match self {
+            LoveError::Multiple { errors } => errors.iter().flat_map(LoveError::errors).collect(),
+            other => vec![other],
+        }
+    }
+
+    /// A short note printed under the caret in diagnostics.
+    pub fn label(&self) -> String {
+        match self {
+            LoveError::Traced { error, .. } => error.label(),
+            LoveError::InvalidToken { .. } => "this isn't a word of love".to_string(),
+            LoveError::Expected { expected, .. } => format!("expected {} here", expected),
+            LoveError::InvalidAssignmentTarget { .. } => "only variables can be matched".to_string(),
+            LoveError::UseBeforeDeclaration { .. } => "used here before its declaration".to_string(),
+            LoveError::DuplicateDeclaration { previous, .. } => {
+                format!("first declared at line {}, column {}", previous.line, previous.column)
+            }
+            LoveError::NestedDevotion { .. } => "declared here, away from the top level".to_string(),
+            LoveError::TypeMismatch { found, .. } => format!("this is a {}", found),
+            LoveError::NonFeelingCondition { found, .. } => format!("this is a {}, not a feeling", found),
+            LoveError::UndefinedVariable { .. } => "not declared in this scope".to_string(),
+            LoveError::UndefinedFunction { .. } => "no devotion by this name".to_string(),
+            LoveError::NotCallable { .. } => "not a devotion".to_string(),
+            LoveError::ArityMismatch { expected, .. } => format!("expected {} arguments", expected),
+            LoveError::DivideByZero { .. } => "the divisor is zero".to_string(),
+            LoveError::Unsupported { .. } => "not supported".to_string(),
+            LoveError::NotALoveFile { .. }
+            | LoveError::Io { .. }
+            | LoveError::Runtime { .. }
+            | LoveError::Multiple { .. } => "heartbreak happened here".to_string(),
+            LoveError::StepLimitExceeded { .. } | LoveError::TimeLimitExceeded { .. } => {
+                "ran out of time here".to_string()
+            }
+            LoveError::CallDepthExceeded { .. } => "one devotion too many".to_string(),
+            LoveError::Cancelled { .. } => "stopped here".to_string(),
+            LoveError::MemoryLimitExceeded { .. } => "this needed more room".to_string(),
+            LoveError::Goodbye { .. } => "said goodbye here".to_string(),
+        }
+    }
+
+    /// A suggestion for fixing the error, when there is a useful one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            LoveError::Traced { error, .. } => error.hint(),
+            LoveError::InvalidToken { .. } => Some(
+                "love stories are made of keywords, names, numbers, \"text\" and punctuation"
+            ),
+            LoveError::Expected { expected, .. } if expected == "';'" => {
+                Some("every love statement ends with a ';'")
+            }
+            LoveError::Expected { expected, .. } if expected == "'{'" || expected == "'}'" => {
+                Some("blocks are wrapped in '{' and '}'")
+            }
+            LoveError::InvalidAssignmentTarget { .. } => Some("write it as `name match value;`"),
+            LoveError::UseBeforeDeclaration { .. } => Some("move the declaration above its first use"),
+            LoveError::DuplicateDeclaration { .. } => {
+                Some("pick another name, or use `name match value;` to change the first one")
+            }
+            LoveError::NestedDevotion { .. } => {
+                Some("move it to the top level of the story; devotions only see globals wherever they are")
+            }
+            LoveError::TypeMismatch { expected: Type::Boolean, .. } => {
+                Some("'and', 'or' and 'not' only work on feelings (yes/no)")
+            }
+            LoveError::TypeMismatch { .. } => {
+                Some("breakup, kiss, split and comparisons only work on numbers; cuddle also joins text")
+            }
+            LoveError::NonFeelingCondition { .. } => Some("compare values with soulmate, admires, envies, ..."),
+            LoveError::UndefinedVariable { .. } | LoveError::UndefinedFunction { .. } => {
+                Some("declare it first with 'heart', 'forever' or 'devotion'")
+            }
+            LoveError::DivideByZero { .. } => Some("check the divisor before you split"),
+            LoveError::CallDepthExceeded { .. } => {
+                Some("make sure every devotion that calls itself has a crush that stops it")
+            }
+            LoveError::NotALoveFile { .. } => Some("rename the file so it ends in .love"),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::iter::Peekable;
+use std::slice;
+
+use crate::lexer::{Trivia, TriviaKind};
+use crate::parser::ast::Ast;
+use crate::shared_types::Value;
+
+const INDENT: &str = "    ";
+
+/// Prints a parsed love story back as source in the canonical style: four
+/// spaces per level, one statement per line, single spaces around
+/// operators, and a blank line around each top-level devotion.
+///
+/// `trivia` is what the lexer kept of the original source. Comments stay
+/// where they were, either on their own line or after the code they
+/// followed, and runs of blank lines are kept as one. A comment in the
+/// middle of an expression moves to just before the next statement.
+pub fn format(ast: &Ast, trivia: &[Trivia]) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        depth: 0,
+        blank: false,
+        trivia: trivia.iter().peekable(),
+    };
+    formatter.statement(ast);
+    formatter.trivia_before(usize::MAX);
+    formatter.out
+}
+
+struct Formatter<'t> {
+    out: String,
+    depth: usize,
+    /// Whether a blank line should come before the next line.
+    blank: bool,
+    trivia: Peekable<slice::Iter<'t, Trivia>>,
+}
+
+impl Formatter<'_> {
+    fn line(&mut self, text: &str) {
+        // No blank lines right inside braces
+        if self.blank && !self.out.is_empty() && !self.out.ends_with("{\n") && !text.starts_with('}') {
+            self.out.push('\n');
+        }
+        self.blank = false;
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Writes out the comments and blank lines that come before `offset`
+    /// in the source.
+    fn trivia_before(&mut self, offset: usize) {
+        while let Some(trivia) = self.trivia.next_if(|t| t.span.start < offset) {
+            match &trivia.kind {
+                TriviaKind::BlankLine => self.blank = true,
+                TriviaKind::Comment(text) if trivia.trailing && !self.out.is_empty() => {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(text.trim_end());
+                    self.out.push('\n');
+                }
+                TriviaKind::Comment(text) => self.line(text.trim_end()),
+            }
+        }
+    }
+
+    fn top_level(&mut self, statements: &[Ast]) {
+        for statement in statements {
+            // The blank line before a devotion goes above its comments
+            let devotion = matches!(statement, Ast::FunctionDecl { .. });
+            self.blank |= devotion;
+            self.trivia_before(statement.span().start);
+            self.statement(statement);
+            self.blank |= devotion;
+        }
+    }
+
+    /// Writes `head {`, the statements indented, and the closing brace,
+    /// keeping the trivia before `end` inside the braces.
+    fn block(&mut self, head: &str, statements: &[Ast], end: usize) {
+        if statements.is_empty() && self.trivia.peek().is_none_or(|t| t.span.start >= end) {
+            self.line(&format!("{}{{}}", head));
+            return;
+        }
+        self.block_open(head, statements, end);
+        self.line("}");
+    }
+
+    /// Like `block`, but leaves the closing brace for whatever follows.
+    fn block_open(&mut self, head: &str, statements: &[Ast], end: usize) {
+        self.line(&format!("{}{{", head));
+        self.depth += 1;
+        for statement in statements {
+            self.trivia_before(statement.span().start);
+            self.statement(statement);
+        }
+        self.trivia_before(end);
+        self.depth -= 1;
+    }
+
+    fn statement(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Program { statements, .. } => self.top_level(statements),
+            Ast::Block { statements, span } => self.block("", statements, span.end),
+            Ast::VariableDecl { name, initializer, is_constant, .. } => {
+                let keyword = if *is_constant { "forever" } else { "heart" };
+                self.line(&format!("{} {} match {};", keyword, name, expression(initializer)));
+            }
+            Ast::FunctionDecl { name, params, return_type, body, span, .. } => {
+                let params: Vec<String> = params.iter().map(|(p, t, _)| format!("{}: {}", p, t)).collect();
+                let returns = return_type.as_ref().map(|t| format!(" -> {}", t)).unwrap_or_default();
+                self.block(&format!("devotion {}({}){} ", name, params.join(", "), returns), body, span.end);
+            }
+            Ast::If { condition, then_branch, else_branch, span } => {
+                let head = format!("crush ({}) ", expression(condition));
+                match else_branch {
+                    Some(else_branch) => {
+                        // The AST doesn't say where 'butterflies' was, so
+                        // trivia up to the first statement after it stays
+                        // in the first branch
+                        let split = else_branch.first().map_or(span.end, |s| s.span().start);
+                        self.block_open(&head, then_branch, split);
+                        self.block("} butterflies ", else_branch, span.end);
+                    }
+                    None => self.block(&head, then_branch, span.end),
+                }
+            }
+            Ast::While { condition, body, span } => {
+                self.block(&format!("dating ({}) ", expression(condition)), body, span.end);
+            }
+            Ast::ExpressionStmt { expr, .. } => self.line(&format!("{};", expression(expr))),
+            Ast::PrintStmt { value, .. } => self.line(&format!("whisper {};", expression(value))),
+            Ast::ReturnStmt { value: Some(value), .. } => self.line(&format!("promise {};", expression(value))),
+            Ast::ReturnStmt { value: None, .. } => self.line("promise;"),
+            expr => self.line(&format!("{};", expression(expr))),
+        }
+    }
+}
+
+/// An expression as it would be written. Parentheses only appear where the
+/// source had them, since the parser keeps them as `Grouping` nodes.
+fn expression(ast: &Ast) -> String {
+    match ast {
+        Ast::Literal { value: Value::Text(text), .. } => format!("\"{}\"", text),
+        Ast::Literal { value, .. } => value.to_string(),
+        Ast::Variable { name, .. } => name.clone(),
+        Ast::Assign { name, value, .. } => format!("{} match {}", name, expression(value)),
+        Ast::Call { callee, arguments, .. } => {
+            let arguments: Vec<String> = arguments.iter().map(expression).collect();
+            format!("{}({})", callee, arguments.join(", "))
+        }
+        Ast::Binary { left, operator, right, .. } => {
+            format!("{} {} {}", expression(left), operator, expression(right))
+        }
+        Ast::Unary { operator, operand, .. } => format!("{} {}", operator, expression(operand)),
+        Ast::Grouping { expr, .. } => format!("({})", expression(expr)),
+        // Statements never appear inside expressions
+        other => format!("{:?}", other),
+    }
+}
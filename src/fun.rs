@@ -1,5 +1,8 @@
+use std::cell::RefCell;
 use colored::*;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 
 // Love-themed emojis and messages
 pub const LOVE_EMOJIS: &[&str] = &["💝", "💖", "💗", "💓", "💕", "💞", "💘", "💟", "❤️", "🫀", "😍", "🥰"];
@@ -23,19 +26,60 @@ pub const ERROR_MESSAGES: &[&str] = &[
     "Love is patient, love is kind, but this code needs fixing! 🔧",
 ];
 
+const LOVE_QUOTES: &[&str] = &[
+    "In code as in love, simplicity is beautiful.",
+    "Every function call is a love letter to the CPU.",
+    "Bug-free code is true love.",
+    "Variables may be undefined, but our love for coding isn't.",
+    "The best code is written with love."
+];
+
+/// Where flavor text comes from.
+enum Flavor {
+    /// A fresh random pick every time.
+    Random,
+    /// Picks from a seeded generator, the same ones for the same seed.
+    Seeded(Box<StdRng>),
+    /// No flavor text at all.
+    Off,
+}
+
+thread_local! {
+    static FLAVOR: RefCell<Flavor> = const { RefCell::new(Flavor::Random) };
+}
+
+/// Makes flavor text on this thread reproducible: the same seed always
+/// picks the same emojis and messages in the same order.
+pub fn seed_flavor(seed: u64) {
+    FLAVOR.with(|flavor| *flavor.borrow_mut() = Flavor::Seeded(Box::new(StdRng::seed_from_u64(seed))));
+}
+
+/// Turns flavor text on this thread off, or back on with random picks.
+/// While it is off, messages are left out and emojis are always the first
+/// one.
+pub fn set_flavor_enabled(enabled: bool) {
+    FLAVOR.with(|flavor| *flavor.borrow_mut() = if enabled { Flavor::Random } else { Flavor::Off });
+}
+
+/// Picks one of `options`, or nothing when flavor text is off.
+fn pick(options: &'static [&'static str]) -> Option<&'static str> {
+    FLAVOR.with(|flavor| match &mut *flavor.borrow_mut() {
+        Flavor::Random => options.choose(&mut rand::thread_rng()).copied(),
+        Flavor::Seeded(rng) => options.choose(rng.as_mut()).copied(),
+        Flavor::Off => None,
+    })
+}
+
 pub fn get_random_emoji() -> String {
-    let mut rng = rand::thread_rng();
-    LOVE_EMOJIS[rng.gen_range(0..LOVE_EMOJIS.len())].to_string()
+    pick(LOVE_EMOJIS).unwrap_or(LOVE_EMOJIS[0]).to_string()
 }
 
-pub fn get_random_success_message() -> String {
-    let mut rng = rand::thread_rng();
-    SUCCESS_MESSAGES[rng.gen_range(0..SUCCESS_MESSAGES.len())].to_string()
+pub fn get_random_success_message() -> Option<String> {
+    pick(SUCCESS_MESSAGES).map(str::to_string)
 }
 
-pub fn get_random_error_message() -> String {
-    let mut rng = rand::thread_rng();
-    ERROR_MESSAGES[rng.gen_range(0..ERROR_MESSAGES.len())].to_string()
+pub fn get_random_error_message() -> Option<String> {
+    pick(ERROR_MESSAGES).map(str::to_string)
 }
 
 pub fn create_love_border(message: &str) -> String {
@@ -67,15 +111,8 @@ pub fn print_love_help() {
 }
 
 pub fn print_random_love_quote() {
-    let quotes = [
-        "In code as in love, simplicity is beautiful.",
-        "Every function call is a love letter to the CPU.",
-        "Bug-free code is true love.",
-        "Variables may be undefined, but our love for coding isn't.",
-        "The best code is written with love."
-    ];
-    let mut rng = rand::thread_rng();
-    let quote = quotes[rng.gen_range(0..quotes.len())];
+    // Asked for by name, so there is always a quote
+    let quote = pick(LOVE_QUOTES).unwrap_or(LOVE_QUOTES[0]);
     println!("{}", create_love_border(quote).bright_magenta());
 }
 
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use crate::error::LoveError;
+use crate::lexer::{Lexer, SpannedToken, Token, TriviaKind};
+use crate::parser::ast::Ast;
+use crate::parser::Parser;
+use crate::span::Span;
+
+/// What a piece of love code is, for coloring it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// `heart`, `devotion`, `crush` and the other words that shape a story.
+    Keyword,
+    /// `cuddle`, `match`, `admires` and the other words that combine values.
+    Operator,
+    /// `number`, `text` and `feeling`.
+    Type,
+    /// Numbers, `"text"`, `yes`, `no` and `lonely`.
+    Literal,
+    /// Names of hearts, forevers and parameters.
+    Identifier,
+    /// Names of devotions, where they are declared and where they are
+    /// called.
+    Function,
+    Comment,
+    /// Parentheses, braces, `;`, `,`, `:` and `->`.
+    Punctuation,
+}
+
+impl Class {
+    /// The name the class goes by in JSON output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword",
+            Class::Operator => "operator",
+            Class::Type => "type",
+            Class::Literal => "literal",
+            Class::Identifier => "identifier",
+            Class::Function => "function",
+            Class::Comment => "comment",
+            Class::Punctuation => "punctuation",
+        }
+    }
+
+    fn of(token: &Token) -> Class {
+        match token {
+            Token::Heart
+            | Token::Forever
+            | Token::Devotion
+            | Token::Crush
+            | Token::Butterflies
+            | Token::Dating
+            | Token::Promise
+            | Token::Whisper
+            | Token::Relationship => Class::Keyword,
+            Token::Cuddle
+            | Token::Breakup
+            | Token::Kiss
+            | Token::Split
+            | Token::Match
+            | Token::Soulmate
+            | Token::Not
+            | Token::And
+            | Token::Or
+            | Token::GreaterThan
+            | Token::GreaterThanEqual
+            | Token::LessThan
+            | Token::LessThanEqual
+            | Token::NotEqual => Class::Operator,
+            Token::TypeNumber | Token::TypeText | Token::TypeFeeling => Class::Type,
+            Token::Number(_) | Token::Text(_) | Token::Yes | Token::No | Token::Lonely => Class::Literal,
+            Token::Identifier(_) => Class::Identifier,
+            Token::Comment(_) => Class::Comment,
+            Token::LParen
+            | Token::RParen
+            | Token::LBrace
+            | Token::RBrace
+            | Token::Semicolon
+            | Token::Comma
+            | Token::Colon
+            | Token::Arrow => Class::Punctuation,
+            // The lexer stops at the first error, so none are classified
+            Token::Error => Class::Punctuation,
+        }
+    }
+}
+
+/// A classified piece of a love story.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Highlight {
+    pub class: Class,
+    pub span: Span,
+}
+
+/// Classifies every token and comment of `source`, in source order, so
+/// editors and documentation can color love code without their own copy
+/// of the keyword list.
+///
+/// Names of devotions are told apart from other names using the parser,
+/// which skips statements that don't parse, so only calls in those show
+/// up as identifiers. A story that doesn't lex can't be classified at all.
+pub fn highlight(source: &str) -> Result<Vec<Highlight>, LoveError> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize()?;
+
+    let mut calls = HashSet::new();
+    let (ast, _) = Parser::new(tokens.clone()).parse_recovering();
+    collect_calls(&ast, &mut calls);
+
+    let mut highlights: Vec<Highlight> = Vec::with_capacity(tokens.len());
+    let mut previous: Option<&SpannedToken> = None;
+    for token in &tokens {
+        let declared = previous.is_some_and(|p| p.token == Token::Devotion);
+        let class = match Class::of(&token.token) {
+            Class::Identifier if declared || calls.contains(&token.span.start) => Class::Function,
+            class => class,
+        };
+        highlights.push(Highlight { class, span: token.span });
+        previous = Some(token);
+    }
+    for trivia in lexer.trivia() {
+        if let TriviaKind::Comment(_) = trivia.kind {
+            highlights.push(Highlight { class: Class::Comment, span: trivia.span });
+        }
+    }
+    highlights.sort_by_key(|h| h.span.start);
+    Ok(highlights)
+}
+
+/// `highlights` of `source` as a JSON array, one object per highlight with
+/// its class, its text, its 1-based line and column, and its byte range.
+pub fn to_json(highlights: &[Highlight], source: &str) -> Value {
+    Value::Array(highlights.iter()
+        .map(|h| json!({
+            "class": h.class.name(),
+            "text": &source[h.span.start..h.span.end],
+            "line": h.span.line,
+            "column": h.span.column,
+            "start": h.span.start,
+            "end": h.span.end,
+        }))
+        .collect())
+}
+
+/// Where the callee of each devotion call in `ast` starts.
+fn collect_calls(ast: &Ast, starts: &mut HashSet<usize>) {
+    ast.walk(&mut |node| {
+        if let Ast::Call { span, .. } = node {
+            starts.insert(span.start);
+        }
+    });
+}
@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::error::LoveError;
+use crate::span::Span;
+
+/// Resource limits that keep a misbehaving love story from taking the host
+/// down with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// How many devotion calls may be nested before the story fails with
+    /// `LoveError::CallDepthExceeded`. Every level uses native stack, tens
+    /// of kilobytes of it in debug builds, so the default needs a thread
+    /// with more than the usual 2 MiB; the `love` binary gives it 64 MiB.
+    pub max_call_depth: usize,
+    /// How many steps a single run may take: AST nodes evaluated by the
+    /// interpreter, instructions executed by the VM.
+    pub max_steps: Option<u64>,
+    /// How long a single run may take, by the wall clock.
+    pub timeout: Option<Duration>,
+    /// Roughly how many bytes of values the story may hold at once.
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_call_depth: 200,
+            max_steps: None,
+            timeout: None,
+            max_memory: None,
+        }
+    }
+}
+
+/// Lets another thread stop a running love story. The interpreter notices
+/// within a few steps and fails with `LoveError::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Clears a pending cancellation, reporting whether there was one.
+    pub(crate) fn take(&self) -> bool {
+        self.cancelled.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// How many steps pass between checks of the deadline and cancel handle.
+const CLOCK_CHECK_INTERVAL: u64 = 64;
+
+impl Limits {
+    /// Fails if holding `extra` more bytes on top of `allocated` would go
+    /// over the memory limit.
+    pub(crate) fn reserve(&self, allocated: usize, extra: usize) -> Result<(), LoveError> {
+        match self.max_memory {
+            Some(limit) if allocated + extra > limit => {
+                Err(LoveError::MemoryLimitExceeded { limit, span: None })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Steps taken and time left in the current run.
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub(crate) fn start(limits: &Limits) -> Self {
+        Budget {
+            steps: 0,
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    /// Charges one step, failing once a limit is exceeded or the story has
+    /// been cancelled.
+    pub(crate) fn tick(&mut self, limits: &Limits, cancel: &CancelHandle, span: Span) -> Result<(), LoveError> {
+        self.steps += 1;
+        if let Some(limit) = limits.max_steps {
+            if self.steps > limit {
+                return Err(LoveError::StepLimitExceeded { limit, span: Some(span) });
+            }
+        }
+        // The clock and the cancellation flag are comparatively slow to
+        // read, so only look at them every so often
+        if self.steps.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            if cancel.take() {
+                return Err(LoveError::Cancelled { span: Some(span) });
+            }
+            if let (Some(deadline), Some(limit)) = (self.deadline, limits.timeout) {
+                if Instant::now() >= deadline {
+                    return Err(LoveError::TimeLimitExceeded { limit, span: Some(span) });
+                }
+            }
+        }
+        Ok(())
+    }
+}
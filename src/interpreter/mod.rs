@@ -1,252 +1,465 @@
 use std::collections::HashMap;
-use crate::shared_types::{BinaryOp, Type, Value};
-use crate::parser::ast::Ast;
-use crate::error::LoveError;
+use std::rc::Rc;
+use std::io::{self, Write};
+use crate::builtins::{Builtin, Context};
+use crate::shared_types::{BinaryOp, Value};
+use crate::parser::ast::{Ast, Slot};
+use crate::resolver::Resolver;
+use crate::error::{LoveError, TraceFrame};
+use crate::span::Span;
+pub use limits::{CancelHandle, Limits};
+pub use output::SharedBuffer;
+pub(crate) use limits::Budget;
 
+mod limits;
+mod output;
+pub mod operators;
+
+/// Variable storage. Globals live in a map and are found by name; locals
+/// live in a stack of scopes and are found by the `Slot` the resolver gave
+/// them. Each devotion call starts a new frame of scopes.
 pub struct Environment {
-    values: HashMap<String, Value>,
+    globals: HashMap<String, Value>,
+    scopes: Vec<Vec<Option<Value>>>,
+    frame_base: usize,
+    bytes: usize,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
-            values: HashMap::new(),
+            globals: HashMap::new(),
+            scopes: Vec::new(),
+            frame_base: 0,
+            bytes: 0,
         }
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
-        self.values.insert(name, value);
+    /// Declares `name` in the innermost scope, or as a global when the
+    /// resolver gave it no slot.
+    pub fn define(&mut self, slot: Option<Slot>, name: &str, value: Value) {
+        self.bytes += value.approx_size();
+        let old = match (slot, self.scopes.last_mut()) {
+            (Some(slot), Some(scope)) => {
+                if scope.len() <= slot.index {
+                    scope.resize(slot.index + 1, None);
+                }
+                scope[slot.index].replace(value)
+            }
+            _ => self.globals.insert(name.to_string(), value),
+        };
+        if let Some(old) = old {
+            self.bytes = self.bytes.saturating_sub(old.approx_size());
+        }
     }
 
-    pub fn get(&self, name: &str) -> Option<&Value> {
-        self.values.get(name)
+    pub fn get(&self, slot: Option<Slot>, name: &str) -> Option<&Value> {
+        match slot {
+            Some(slot) => self.local(slot)?.as_ref(),
+            None => self.globals.get(name),
+        }
     }
 
-    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), LoveError> {
-        if self.values.contains_key(name) {
-            self.values.insert(name.to_string(), value);
-            Ok(())
-        } else {
-            Err(LoveError::Runtime(format!("Undefined variable '{}'.", name)))
+    pub fn assign(&mut self, slot: Option<Slot>, name: &str, value: Value) -> Result<(), LoveError> {
+        let replaced = self.get(slot, name)
+            .ok_or_else(|| LoveError::UndefinedVariable { name: name.to_string(), span: None })?
+            .approx_size();
+        self.bytes = (self.bytes + value.approx_size()).saturating_sub(replaced);
+        match slot {
+            Some(slot) => {
+                if let Some(local) = self.local_mut(slot) {
+                    *local = Some(value);
+                }
+            }
+            None => {
+                self.globals.insert(name.to_string(), value);
+            }
         }
+        Ok(())
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            self.release(scope);
+        }
+    }
+
+    /// Starts a devotion call frame, returning what `exit_frame` needs to
+    /// restore the caller's view.
+    pub fn enter_frame(&mut self) -> usize {
+        let saved = self.frame_base;
+        self.frame_base = self.scopes.len();
+        self.scopes.push(Vec::new());
+        saved
+    }
+
+    pub fn exit_frame(&mut self, saved: usize) {
+        while self.scopes.len() > self.frame_base {
+            self.pop_scope();
+        }
+        self.frame_base = saved;
+    }
+
+    /// Approximate bytes held by every value currently stored.
+    pub fn allocated(&self) -> usize {
+        self.bytes
+    }
+
+    fn release(&mut self, scope: Vec<Option<Value>>) {
+        let held: usize = scope.iter().flatten().map(Value::approx_size).sum();
+        self.bytes = self.bytes.saturating_sub(held);
+    }
+
+    /// The local at `slot`, counting scopes out from the innermost one but
+    /// never past the current frame.
+    fn local(&self, slot: Slot) -> Option<&Option<Value>> {
+        let scope = self.scopes.len().checked_sub(slot.depth + 1)?;
+        if scope < self.frame_base {
+            return None;
+        }
+        self.scopes[scope].get(slot.index)
+    }
+
+    fn local_mut(&mut self, slot: Slot) -> Option<&mut Option<Value>> {
+        let scope = self.scopes.len().checked_sub(slot.depth + 1)?;
+        if scope < self.frame_base {
+            return None;
+        }
+        self.scopes[scope].get_mut(slot.index)
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 pub struct Interpreter {
+    resolver: Resolver,
     environment: Environment,
+    output: Box<dyn Write>,
+    call_stack: Vec<TraceFrame>,
+    returning: Option<Value>,
+    limits: Limits,
+    budget: Budget,
+    cancel: CancelHandle,
+    context: Context,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Interpreter::with_output(Box::new(io::stdout()))
+    }
+
+    /// Creates an interpreter whose `whisper` statements write to `output`
+    /// instead of stdout.
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        // Builtins come with every story, so they don't count against its
+        // memory limit
+        let mut environment = Environment::new();
+        for builtin in Builtin::ALL {
+            environment.globals.insert(builtin.name().to_string(), Value::Builtin(builtin));
+        }
         Interpreter {
-            environment: Environment::new(),
+            resolver: Resolver::new(),
+            environment,
+            output,
+            call_stack: Vec::new(),
+            returning: None,
+            limits: Limits::default(),
+            budget: Budget::default(),
+            cancel: CancelHandle::default(),
+            context: Context::default(),
         }
     }
 
-      fn check_type(&self, value: &Value, expected: Type) -> Result<(), LoveError> {
-        let actual = value.get_type();
-        if actual != expected {
-            Err(LoveError::Type(format!(
-                "Expected {}, but found {}",
-                expected,
-                value
-            )))
-        } else {
-            Ok(())
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// A handle that can cancel whatever this interpreter is running, from
+    /// any thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Redirects `whisper` output, returning the previous sink.
+    pub fn set_output(&mut self, output: Box<dyn Write>) -> Box<dyn Write> {
+        std::mem::replace(&mut self.output, output)
+    }
+
+    /// Sets the arguments stories read with `letters()` and `letter(n)`.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.context.args = args;
+    }
+
+    /// Whether stories may read environment variables with `secret(name)`.
+    /// Off by default.
+    pub fn set_allow_env(&mut self, allowed: bool) {
+        self.context.allow_env = allowed;
+    }
+
+    /// Runs statements in order, yielding the value of the last one. Stops
+    /// early once a 'promise' has been kept.
+    fn execute_statements(&mut self, statements: &[Ast]) -> Result<Value, LoveError> {
+        let mut result = Value::Null;
+        for stmt in statements {
+            result = self.evaluate(stmt)?;
+            if self.returning.is_some() {
+                break;
+            }
         }
+        Ok(result)
     }
 
-    fn check_binary_operands(
-        &self,
-        left: &Value,
-        right: &Value,
-        _operator: &BinaryOp,
-        expected_type: Type
-    ) -> Result<(), LoveError> {
-        self.check_type(left, expected_type.clone())?;
-        self.check_type(right, expected_type.clone())?;
-        Ok(())
+    /// Resolves and runs a parsed love story (or a single statement of one)
+    /// and returns the value of the last statement. Each call gets a fresh
+    /// execution budget as configured in `Limits`.
+    pub fn interpret(&mut self, mut ast: Ast) -> Result<Value, LoveError> {
+        self.resolver.resolve(&mut ast)?;
+        self.budget = Budget::start(&self.limits);
+        let result = self.evaluate(&ast);
+        self.returning = None;
+        result
+    }
+
+    /// Charges one step against the execution budget, failing once a limit
+    /// is exceeded or the story has been cancelled.
+    fn tick(&mut self, span: Span) -> Result<(), LoveError> {
+        self.budget.tick(&self.limits, &self.cancel, span)
     }
 
-    pub fn interpret(&mut self, ast: Ast) -> Result<Value, LoveError> {
+    /// Fails if holding `extra` more bytes would take the story over its
+    /// memory limit.
+    fn reserve(&self, extra: usize) -> Result<(), LoveError> {
+        self.limits.reserve(self.environment.allocated(), extra)
+    }
+
+    fn evaluate(&mut self, ast: &Ast) -> Result<Value, LoveError> {
+        self.tick(ast.span())?;
         match ast {
-            Ast::Program(statements) => {
-                let mut result = Value::Null;
-                for stmt in statements {
-                    result = self.interpret(stmt)?;
-                }
-                Ok(result)
+            Ast::Program { statements, .. } => {
+                let result = self.execute_statements(statements);
+                // A top-level 'promise' ends the story early
+                let returned = self.returning.take();
+                Ok(returned.unwrap_or(result?))
             }
-            Ast::FunctionDecl { name, params, body, .. } => {
-                let param_names = params.into_iter().map(|(name, _)| name).collect();
+            Ast::FunctionDecl { name, params, body, slot, span, .. } => {
                 let function = Value::Function {
                     name: name.clone(),
-                    params: param_names,
-                    body,
+                    params: params.iter().map(|(name, _, _)| name.clone()).collect(),
+                    body: Rc::clone(body),
                 };
-                self.environment.define(name, function.clone());
+                self.reserve(function.approx_size()).map_err(|e| e.with_span(*span))?;
+                self.environment.define(*slot, name, function.clone());
                 Ok(function)
             }
-            Ast::Call { callee, arguments } => {
-                let function = self.environment.get(&callee)
-                    .ok_or_else(|| LoveError::Runtime(format!("Undefined function '{}'", callee)))?
+            Ast::Call { callee, arguments, slot, span } => {
+                let span = *span;
+                let function = self.environment.get(*slot, callee)
+                    .ok_or_else(|| LoveError::UndefinedFunction { name: callee.clone(), span: Some(span) })?
                     .clone();
 
                 match function {
                     Value::Function { params, body, .. } => {
-                        // Create new environment for function scope
-                        let mut new_env = Environment::new();
-
                         // Evaluate and bind arguments
                         if params.len() != arguments.len() {
-                            return Err(LoveError::Runtime(format!(
-                                "Expected {} arguments but got {}.",
-                                params.len(),
-                                arguments.len()
-                            )));
+                            return Err(LoveError::ArityMismatch {
+                                name: callee.clone(),
+                                expected: params.len(),
+                                got: arguments.len(),
+                                span: Some(span),
+                            });
                         }
 
-                        for (param, arg) in params.iter().zip(arguments) {
-                            let value = self.interpret(arg)?;
-                            new_env.define(param.clone(), value);
+                        let mut values = Vec::with_capacity(arguments.len());
+                        for arg in arguments {
+                            values.push(self.evaluate(arg)?);
                         }
 
-                        // Store current environment and set new one
-                        let old_env = std::mem::replace(&mut self.environment, new_env);
+                        if self.call_stack.len() >= self.limits.max_call_depth {
+                            return Err(LoveError::CallDepthExceeded {
+                                limit: self.limits.max_call_depth,
+                                span: Some(span),
+                            });
+                        }
 
-                        // Execute function body
-                        let mut result = Value::Null;
-                        for stmt in body {
-                            result = self.interpret(stmt)?;
+                        // Enter a new frame for the function scope
+                        let saved = self.environment.enter_frame();
+                        for (index, (param, value)) in params.iter().zip(values).enumerate() {
+                            self.environment.define(Some(Slot { depth: 0, index }), param, value);
                         }
+                        self.call_stack.push(TraceFrame { function: callee.clone(), call_site: span });
 
-                        // Restore old environment
-                        self.environment = old_env;
+                        // Execute function body; a kept promise wins over
+                        // the value of the last statement
+                        let result = self.execute_statements(&body)
+                            .map(|value| self.returning.take().unwrap_or(value));
+                        self.returning = None;
 
-                        Ok(result)
+                        // Errors leaving their innermost devotion capture the
+                        // call stack as it was at the point of failure
+                        let result = result.map_err(|e| match e {
+                            LoveError::Traced { .. } | LoveError::Goodbye { .. } => e,
+                            e => LoveError::Traced {
+                                error: Box::new(e),
+                                trace: self.call_stack.iter().rev().cloned().collect(),
+                            },
+                        });
+
+                        // Restore the caller's frame
+                        self.call_stack.pop();
+                        self.environment.exit_frame(saved);
+
+                        result
+                    }
+                    Value::Builtin(builtin) => {
+                        if builtin.params().len() != arguments.len() {
+                            return Err(LoveError::ArityMismatch {
+                                name: callee.clone(),
+                                expected: builtin.params().len(),
+                                got: arguments.len(),
+                                span: Some(span),
+                            });
+                        }
+                        let mut values = Vec::with_capacity(arguments.len());
+                        for arg in arguments {
+                            values.push(self.evaluate(arg)?);
+                        }
+                        builtin.call(values, &self.context).map_err(|e| e.with_span(span))
                     }
-                    _ => Err(LoveError::Runtime(format!("'{}' is not a function", callee))),
+                    _ => Err(LoveError::NotCallable { name: callee.clone(), span: Some(span) }),
                 }
             }
-            Ast::ReturnStmt(value) => {
-                match value {
-                    Some(expr) => self.interpret(*expr),
-                    None => Ok(Value::Null),
-                }
+            Ast::ReturnStmt { value, .. } => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Null,
+                };
+                self.returning = Some(value.clone());
+                Ok(value)
             }
-            Ast::VariableDecl { name, initializer, .. } => {
-                let value = self.interpret(*initializer)?;
-                self.environment.define(name, value.clone());
+            Ast::VariableDecl { name, initializer, slot, span, .. } => {
+                let value = self.evaluate(initializer)?;
+                self.reserve(value.approx_size()).map_err(|e| e.with_span(*span))?;
+                self.environment.define(*slot, name, value.clone());
                 Ok(value)
             }
-              Ast::If { condition, then_branch, else_branch } => {
+              Ast::If { condition, then_branch, else_branch, .. } => {
                 // Evaluate the condition
-                let cond_value = self.interpret(*condition)?;
+                let condition_span = condition.span();
+                let cond_value = self.evaluate(condition)?;
                 
                 match cond_value {
                     Value::Boolean(true) => {
                         // Execute then branch
-                        let mut last_value = Value::Null;
-                        for stmt in then_branch {
-                            last_value = self.interpret(stmt)?;
-                        }
-                        Ok(last_value)
+                        self.execute_statements(then_branch)
                     },
                     Value::Boolean(false) => {
                         // Execute else branch if it exists
-                        if let Some(else_stmts) = else_branch {
-                            let mut last_value = Value::Null;
-                            for stmt in else_stmts {
-                                last_value = self.interpret(stmt)?;
-                            }
-                            Ok(last_value)
+                        if let Some(else_stmts) = else_branch.as_deref() {
+                            self.execute_statements(else_stmts)
                         } else {
                             Ok(Value::Null)
                         }
                     },
-                    _ => Err(LoveError::Runtime(
-                        "Condition must evaluate to a feeling (yes/no)".to_string()
-                    )),
+                    other => Err(LoveError::NonFeelingCondition {
+                        found: other.get_type(),
+                        span: Some(condition_span),
+                    }),
                 }
             },
-            Ast::Binary { left, operator, right } => {
-                let left_val = self.interpret(*left)?;
-                let right_val = self.interpret(*right)?;
-                
-                 match operator {
-                    BinaryOp::Add | BinaryOp::Subtract | 
-                    BinaryOp::Multiply | BinaryOp::Divide => {
-                        self.check_binary_operands(&left_val, &right_val, &operator, Type::Number)?;
-                        
-                        match (left_val, operator, right_val) {
-                            (Value::Number(a), BinaryOp::Add, Value::Number(b)) => 
-                                Ok(Value::Number(a + b)),
-                            (Value::Number(a), BinaryOp::Subtract, Value::Number(b)) => 
-                                Ok(Value::Number(a - b)),
-                            (Value::Number(a), BinaryOp::Multiply, Value::Number(b)) => 
-                                Ok(Value::Number(a * b)),
-                            (Value::Number(a), BinaryOp::Divide, Value::Number(b)) => {
-                                if b == 0.0 {
-                                    Err(LoveError::Runtime("Cannot split by zero!".to_string()))
-                                } else {
-                                    Ok(Value::Number(a / b))
-                                }
-                            }
-                            _ => Err(LoveError::Runtime("Invalid operation".to_string())),
-                        }
-                    },
-                    BinaryOp::Greater | BinaryOp::Less | 
-                    BinaryOp::GreaterEqual | BinaryOp::LessEqual => {
-                        self.check_binary_operands(&left_val, &right_val, &operator, Type::Number)?;
-                        
-                        match (left_val, operator, right_val) {
-                            (Value::Number(a), BinaryOp::Greater, Value::Number(b)) => 
-                                Ok(Value::Boolean(a > b)),
-                            (Value::Number(a), BinaryOp::Less, Value::Number(b)) => 
-                                Ok(Value::Boolean(a < b)),
-                            (Value::Number(a), BinaryOp::GreaterEqual, Value::Number(b)) => 
-                                Ok(Value::Boolean(a >= b)),
-                            (Value::Number(a), BinaryOp::LessEqual, Value::Number(b)) => 
-                                Ok(Value::Boolean(a <= b)),
-                            _ => Err(LoveError::Runtime("Invalid comparison".to_string())),
-                        }
-                    },
-                    _ => Err(LoveError::Runtime("Operation not implemented".to_string())),
+            Ast::Binary { left, operator, right, span } => {
+                let span = *span;
+                let left_val = self.evaluate(left)?;
+
+                // 'and' and 'or' only look at the right side when they must
+                let decided = match operator {
+                    BinaryOp::And => !operators::feeling(&left_val).map_err(|e| e.with_span(span))?,
+                    BinaryOp::Or => operators::feeling(&left_val).map_err(|e| e.with_span(span))?,
+                    _ => false,
+                };
+                if decided {
+                    return Ok(left_val);
+                }
+
+                let right_val = self.evaluate(right)?;
+                if let Some(size) = operators::allocation_size(&left_val, operator, &right_val) {
+                    self.reserve(size).map_err(|e| e.with_span(span))?;
                 }
+                operators::binary(left_val, operator, right_val)
+                    .map_err(|e| e.with_span(span))
             }
-            Ast::PrintStmt(expr) => {
-                let value = self.interpret(*expr)?;
-                println!("{:?}", value);
+            Ast::Unary { operator, operand, span } => {
+                let value = self.evaluate(operand)?;
+                operators::unary(operator, value).map_err(|e| e.with_span(*span))
+            }
+            Ast::PrintStmt { value, span } => {
+                let value = self.evaluate(value)?;
+                writeln!(self.output, "{}", value)
+                    .map_err(|e| LoveError::io("whisper", e).with_span(*span))?;
                 Ok(Value::Null)
             }
-            Ast::Literal(value) => Ok(value),
-            Ast::Variable(name) => {
-                self.environment.get(&name)
+            Ast::Literal { value, .. } => Ok(value.clone()),
+            Ast::Variable { name, slot, span } => {
+                self.environment.get(*slot, name)
                     .cloned()
-                    .ok_or_else(|| LoveError::Runtime(format!("Undefined variable '{}'.", name)))
+                    .ok_or_else(|| LoveError::UndefinedVariable { name: name.clone(), span: Some(*span) })
             }
-            Ast::Assign { name, value } => {
-                let evaluated_value = self.interpret(*value)?;
-                self.environment.assign(&name, evaluated_value.clone())?;
+            Ast::Assign { name, value, slot, span } => {
+                let evaluated_value = self.evaluate(value)?;
+                let replaced = self.environment.get(*slot, name).map_or(0, Value::approx_size);
+                self.reserve(evaluated_value.approx_size().saturating_sub(replaced))
+                    .map_err(|e| e.with_span(*span))?;
+                self.environment.assign(*slot, name, evaluated_value.clone())
+                    .map_err(|e| e.with_span(*span))?;
                 Ok(evaluated_value)
             }
-            Ast::ExpressionStmt(expr) => self.interpret(*expr),
-            Ast::Grouping(expr) => self.interpret(*expr),
-            Ast::Block(statements) => {
-                let mut result = Value::Null;
-                // Create new environment for block scope
-                let new_env = Environment::new();
-                let old_env = std::mem::replace(&mut self.environment, new_env);
-
-                for stmt in statements {
-                    result = self.interpret(stmt)?;
+            Ast::ExpressionStmt { expr, .. } => self.evaluate(expr),
+            Ast::Grouping { expr, .. } => self.evaluate(expr),
+            Ast::Block { statements, .. } => {
+                // Create new scope for the block
+                self.environment.push_scope();
+                let result = self.execute_statements(statements);
+                self.environment.pop_scope();
+                result
+            }
+            Ast::While { condition, body, .. } => {
+                let condition_span = condition.span();
+                loop {
+                    match self.evaluate(condition)? {
+                        Value::Boolean(true) => {
+                            self.execute_statements(body)?;
+                            if self.returning.is_some() {
+                                break;
+                            }
+                        }
+                        Value::Boolean(false) => break,
+                        other => {
+                            return Err(LoveError::NonFeelingCondition {
+                                found: other.get_type(),
+                                span: Some(condition_span),
+                            })
+                        }
+                    }
                 }
-
-                // Restore old environment
-                self.environment = old_env;
-                Ok(result)
+                Ok(Value::Null)
             }
-            _ => Err(LoveError::Runtime("Not implemented".to_string())),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,106 @@
+//! The meaning of each love operator, shared by every execution engine so
+//! they agree on results and errors.
+
+use crate::error::LoveError;
+use crate::shared_types::{BinaryOp, Type, Value};
+
+pub fn check_type(value: &Value, expected: Type) -> Result<(), LoveError> {
+    let actual = value.get_type();
+    if actual != expected {
+        Err(LoveError::TypeMismatch { expected, found: actual, span: None })
+    } else {
+        Ok(())
+    }
+}
+
+fn check_binary_operands(left: &Value, right: &Value, expected_type: Type) -> Result<(), LoveError> {
+    check_type(left, expected_type.clone())?;
+    check_type(right, expected_type)?;
+    Ok(())
+}
+
+/// Reads a feeling, as needed by 'and', 'or' and 'not'.
+pub fn feeling(value: &Value) -> Result<bool, LoveError> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        other => Err(LoveError::TypeMismatch { expected: Type::Boolean, found: other.get_type(), span: None }),
+    }
+}
+
+/// How many bytes applying `operator` would allocate, if it builds new text.
+/// Engines use this to enforce memory limits before the allocation happens.
+pub fn allocation_size(left: &Value, operator: &BinaryOp, right: &Value) -> Option<usize> {
+    match operator {
+        BinaryOp::Add if is_text(left) || is_text(right) => {
+            Some(left.approx_size() + right.approx_size())
+        }
+        _ => None,
+    }
+}
+
+fn is_text(value: &Value) -> bool {
+    matches!(value, Value::Text(_))
+}
+
+/// Applies a binary love operator to two evaluated operands. 'and' and 'or'
+/// are included for completeness; engines short-circuit them before
+/// evaluating the right operand.
+pub fn binary(left_val: Value, operator: &BinaryOp, right_val: Value) -> Result<Value, LoveError> {
+    match operator {
+        // Cuddling text joins it with whatever it cuddles
+        BinaryOp::Add if is_text(&left_val) || is_text(&right_val) => {
+            Ok(Value::Text(format!("{}{}", left_val, right_val)))
+        },
+        BinaryOp::Add | BinaryOp::Subtract | 
+        BinaryOp::Multiply | BinaryOp::Divide => {
+            check_binary_operands(&left_val, &right_val, Type::Number)?;
+            
+            match (left_val, operator, right_val) {
+                (Value::Number(a), BinaryOp::Add, Value::Number(b)) => 
+                    Ok(Value::Number(a + b)),
+                (Value::Number(a), BinaryOp::Subtract, Value::Number(b)) => 
+                    Ok(Value::Number(a - b)),
+                (Value::Number(a), BinaryOp::Multiply, Value::Number(b)) => 
+                    Ok(Value::Number(a * b)),
+                (Value::Number(a), BinaryOp::Divide, Value::Number(b)) => {
+                    if b == 0.0 {
+                        Err(LoveError::DivideByZero { span: None })
+                    } else {
+                        Ok(Value::Number(a / b))
+                    }
+                }
+                _ => Err(LoveError::runtime("Invalid operation")),
+            }
+        },
+        BinaryOp::Greater | BinaryOp::Less | 
+        BinaryOp::GreaterEqual | BinaryOp::LessEqual => {
+            check_binary_operands(&left_val, &right_val, Type::Number)?;
+            
+            match (left_val, operator, right_val) {
+                (Value::Number(a), BinaryOp::Greater, Value::Number(b)) => 
+                    Ok(Value::Boolean(a > b)),
+                (Value::Number(a), BinaryOp::Less, Value::Number(b)) => 
+                    Ok(Value::Boolean(a < b)),
+                (Value::Number(a), BinaryOp::GreaterEqual, Value::Number(b)) => 
+                    Ok(Value::Boolean(a >= b)),
+                (Value::Number(a), BinaryOp::LessEqual, Value::Number(b)) => 
+                    Ok(Value::Boolean(a <= b)),
+                _ => Err(LoveError::runtime("Invalid comparison")),
+            }
+        },
+        // Any two values can be soulmates, even of different types
+        BinaryOp::Equal => Ok(Value::Boolean(left_val == right_val)),
+        BinaryOp::NotEqual => Ok(Value::Boolean(left_val != right_val)),
+        BinaryOp::And => Ok(Value::Boolean(feeling(&left_val)? && feeling(&right_val)?)),
+        BinaryOp::Or => Ok(Value::Boolean(feeling(&left_val)? || feeling(&right_val)?)),
+        BinaryOp::Not => Err(LoveError::Unsupported { operation: format!("'{}'", operator), span: None }),
+    }
+}
+
+/// Applies a unary love operator.
+pub fn unary(operator: &BinaryOp, operand: Value) -> Result<Value, LoveError> {
+    match operator {
+        BinaryOp::Not => Ok(Value::Boolean(!feeling(&operand)?)),
+        _ => Err(LoveError::Unsupported { operation: format!("'{}'", operator), span: None }),
+    }
+}
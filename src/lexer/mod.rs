@@ -1,14 +1,41 @@
 use logos::Logos;
 use crate::error::LoveError;
+use crate::span::Span;
 pub use token::Token;
 
 mod token;
 
+/// A token together with where it was found in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+/// Source text the parser doesn't need but a formatter must keep.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriviaKind {
+    /// A `//` comment, including the slashes.
+    Comment(String),
+    /// One or more empty lines between two tokens.
+    BlankLine,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+    /// Whether code comes before it on the same line.
+    pub trailing: bool,
+}
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
     source: &'a str,
     line: usize,
     column: usize,
+    offset: usize,
+    trivia: Vec<Trivia>,
 }
 
 impl<'a> Lexer<'a> {
@@ -17,39 +44,69 @@ impl<'a> Lexer<'a> {
             source,
             line: 1,
             column: 1,
+            offset: 0,
+            trivia: Vec::new(),
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, LoveError> {
+    /// The comments and blank lines found by `tokenize`, in source order.
+    pub fn trivia(&self) -> &[Trivia] {
+        &self.trivia
+    }
+
+    pub fn tokenize(&mut self) -> Result<Vec<SpannedToken>, LoveError> {
         let mut tokens = Vec::new();
         let mut lexer = Token::lexer(self.source);
-        
+        // Where the previous token or comment ended
+        let mut previous_end = None;
+
         while let Some(token) = lexer.next() {
-            // Update position information
-            let span = lexer.span();
-            let slice = &self.source[..span.start];
-            let lines = slice.matches('\n').count();
-            self.line += lines;
-            if lines > 0 {
-                self.column = span.start - slice.rfind('\n').unwrap_or(0);
-            } else {
-                self.column += span.end - span.start;
+            let range = lexer.span();
+            let gap = &self.source[previous_end.unwrap_or(0)..range.start];
+            if let (Some(end), Some(first)) = (previous_end, gap.find('\n')) {
+                if gap[first + 1..].contains('\n') {
+                    let blank = end + first + 1;
+                    let span = self.span_for(blank, blank);
+                    self.trivia.push(Trivia { kind: TriviaKind::BlankLine, span, trailing: false });
+                }
             }
+            let trailing = previous_end.is_some() && !gap.contains('\n');
+            previous_end = Some(range.end);
+            let span = self.span_for(range.start, range.end);
 
             // Handle the token
             match token {
                 Token::Error => {
-                    return Err(LoveError::Lexer(format!(
-                        "Invalid token at line {}, column {}",
-                        self.line,
-                        self.column
-                    )))
+                    // The catch-all pattern matches a single byte, which
+                    // may be just the start of a character
+                    let text: String = self.source[range.start..].chars().take(1).collect();
+                    return Err(LoveError::InvalidToken {
+                        span: Span { end: range.start + text.len(), ..span },
+                        text,
+                    })
+                }
+                Token::Comment(text) => {
+                    self.trivia.push(Trivia { kind: TriviaKind::Comment(text), span, trailing });
                 }
-                token => tokens.push(token),
+                token => tokens.push(SpannedToken { token, span }),
             }
         }
 
         Ok(tokens)
     }
 
+    /// Builds the span for `start..end`, advancing the line/column counters
+    /// over the text skipped since the previous token.
+    fn span_for(&mut self, start: usize, end: usize) -> Span {
+        for c in self.source[self.offset..start].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset = start;
+        Span::new(start, end, self.line, self.column)
+    }
 }
@@ -5,7 +5,6 @@ use std::fmt;
 pub enum Token {
 
     #[regex(r"[ \t\n\f]+", logos::skip)]  // Skip whitespace
-    #[regex(r"//[^\n]*\n?", logos::skip)]  // Skip line comments
 
     #[error]
     #[regex(r".", priority = 0)]  // Catch any single char for error reporting
@@ -101,15 +100,56 @@ pub enum Token {
     #[token("->")]
     Arrow,
 
+    // Line comments, kept as trivia by the lexer rather than parsed
+    #[regex(r"//[^\n]*", |lex| Some(String::from(lex.slice())))]
+    Comment(String),
+
     // Identifiers (must come after keywords)
     #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| Some(String::from(lex.slice())))]
     Identifier(String),
 }
 
+impl Token {
+    /// Every word the language reserves, for tools that offer or highlight
+    /// them without keeping their own list.
+    pub const WORDS: [Token; 29] = [
+        Token::Heart,
+        Token::Forever,
+        Token::Devotion,
+        Token::Crush,
+        Token::Butterflies,
+        Token::Lonely,
+        Token::Dating,
+        Token::Promise,
+        Token::Whisper,
+        Token::Relationship,
+        Token::TypeNumber,
+        Token::TypeText,
+        Token::TypeFeeling,
+        Token::Yes,
+        Token::No,
+        Token::Cuddle,
+        Token::Breakup,
+        Token::Kiss,
+        Token::Split,
+        Token::Match,
+        Token::Soulmate,
+        Token::Not,
+        Token::And,
+        Token::Or,
+        Token::GreaterThan,
+        Token::GreaterThanEqual,
+        Token::LessThan,
+        Token::LessThanEqual,
+        Token::NotEqual,
+    ];
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Token::Error => write!(f, "ERROR"),
+            Token::Comment(text) => write!(f, "{}", text),
             Token::Heart => write!(f, "heart"),
             Token::Forever => write!(f, "forever"),
             Token::Devotion => write!(f, "devotion"),
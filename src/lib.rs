@@ -0,0 +1,19 @@
+pub mod shared_types;
+pub mod lexer;
+pub mod parser;
+pub mod resolver;
+mod scopes;
+pub mod optimizer;
+pub mod checker;
+pub mod formatter;
+pub mod highlight;
+pub mod linter;
+pub mod lsp;
+pub mod builtins;
+pub mod interpreter;
+pub mod error;
+pub mod diagnostic;
+pub mod fun;
+pub mod runner;
+pub mod vm;
+pub mod span;
@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::{Diagnostic, Level};
+use crate::optimizer::{collect_assigned, optimize};
+use crate::parser::ast::Ast;
+use crate::scopes::Scopes;
+use crate::shared_types::Value;
+use crate::span::Span;
+
+/// What to do about a rule's findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Don't report them.
+    Allow,
+    /// Report them as warnings.
+    Warn,
+    /// Report them as errors, failing the lint.
+    Deny,
+}
+
+/// A likely mistake `love lint` looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// A `heart` or `forever` that is never read.
+    UnusedHeart,
+    /// Statements after a `promise`, which never run.
+    AfterPromise,
+    /// A `crush` or `dating` condition that is always `yes`.
+    AlwaysYes,
+    /// A `heart` inside a devotion with the name of one of its parameters.
+    ShadowedVow,
+    /// A `forever` whose name isn't in capitals.
+    QuietForever,
+    /// A `dating` loop whose body never changes the names its condition
+    /// reads, so it either never runs or never ends.
+    StuckDating,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedHeart,
+        Rule::AfterPromise,
+        Rule::AlwaysYes,
+        Rule::ShadowedVow,
+        Rule::QuietForever,
+        Rule::StuckDating,
+    ];
+
+    /// The name the rule goes by on the command line and in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedHeart => "unused-heart",
+            Rule::AfterPromise => "after-promise",
+            Rule::AlwaysYes => "always-yes",
+            Rule::ShadowedVow => "shadowed-vow",
+            Rule::QuietForever => "quiet-forever",
+            Rule::StuckDating => "stuck-dating",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    /// How seriously the rule is taken unless configured otherwise. Only a
+    /// loop that can't end is denied; everything else might be on purpose.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::StuckDating => Severity::Deny,
+            _ => Severity::Warn,
+        }
+    }
+}
+
+/// The severity of each rule for one run of the linter.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    overrides: HashMap<Rule, Severity>,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, rule: Rule, severity: Severity) {
+        self.overrides.insert(rule, severity);
+    }
+
+    /// Sets the severity of every rule at once.
+    pub fn set_all(&mut self, severity: Severity) {
+        for rule in Rule::ALL {
+            self.set(rule, severity);
+        }
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.overrides.get(&rule).copied().unwrap_or(rule.default_severity())
+    }
+}
+
+/// One finding of the linter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    pub label: &'static str,
+    pub hint: &'static str,
+    pub span: Span,
+}
+
+impl Lint {
+    /// The lint as a report, a warning or an error depending on its
+    /// severity.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(self.message.clone());
+        diagnostic.level = match self.severity {
+            Severity::Deny => Level::Error,
+            Severity::Warn | Severity::Allow => Level::Warning,
+        };
+        diagnostic.code = Some(self.rule.name());
+        diagnostic.span = Some(self.span);
+        diagnostic.label = Some(self.label.to_string());
+        diagnostic.hint = Some(self.hint.to_string());
+        diagnostic
+    }
+}
+
+/// Looks for likely mistakes in a parsed love story without running it,
+/// returning what the rules `config` doesn't allow found, in source order.
+///
+/// Unlike the checker, the linter reports code that would run fine but
+/// probably doesn't do what its author meant.
+pub fn lint(ast: &Ast, config: &LintConfig) -> Vec<Lint> {
+    let mut linter = Linter {
+        config,
+        lints: Vec::new(),
+        scopes: Scopes::new(),
+        global_reads: HashSet::new(),
+        params: Vec::new(),
+    };
+    linter.statement(ast);
+    let globals = std::mem::replace(&mut linter.scopes, Scopes::new()).into_globals();
+    for heart in globals.into_values() {
+        if !heart.read && !linter.global_reads.contains(&heart.name) {
+            linter.unused(heart);
+        }
+    }
+    let mut lints = linter.lints;
+    lints.sort_by_key(|lint| lint.span.start);
+    lints
+}
+
+/// A declared name and whether anything has read it yet.
+struct Heart {
+    name: String,
+    span: Span,
+    read: bool,
+}
+
+struct Linter<'c> {
+    config: &'c LintConfig,
+    lints: Vec<Lint>,
+    scopes: Scopes<Heart>,
+    /// Names read that weren't locals. Devotions may read globals before
+    /// they are declared, so whether a global was read is only known at
+    /// the end.
+    global_reads: HashSet<String>,
+    /// Parameters of the devotions being linted, innermost last.
+    params: Vec<Vec<String>>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: Rule, span: Span, message: String, label: &'static str, hint: &'static str) {
+        let severity = self.config.severity(rule);
+        if severity != Severity::Allow {
+            self.lints.push(Lint { rule, severity, message, label, hint, span });
+        }
+    }
+
+    /// Declares `name`. Only `heart`s and `forever`s need reading, so
+    /// devotions and parameters start out as read.
+    fn declare(&mut self, name: &str, span: Span, read: bool) {
+        self.scopes.declare(name, Heart { name: name.to_string(), span, read });
+    }
+
+    fn read(&mut self, name: &str) {
+        match self.scopes.local_mut(name) {
+            Some(heart) => heart.read = true,
+            None => {
+                self.global_reads.insert(name.to_string());
+            }
+        }
+    }
+
+    fn end_scope(&mut self, scope: HashMap<String, Heart>) {
+        for heart in scope.into_values() {
+            if !heart.read {
+                self.unused(heart);
+            }
+        }
+    }
+
+    fn unused(&mut self, heart: Heart) {
+        if heart.name.starts_with('_') {
+            return;
+        }
+        self.report(
+            Rule::UnusedHeart,
+            heart.span,
+            format!("'{}' was given a heart, but nobody ever looks at it", heart.name),
+            "never read",
+            "read it somewhere, or start its name with '_' if the love is meant to be unrequited",
+        );
+    }
+
+    /// Lints a statement list, reporting the first statement that can't
+    /// run because an earlier one always makes a promise.
+    fn statements(&mut self, statements: &[Ast]) {
+        let mut promised = false;
+        for statement in statements {
+            if promised {
+                self.report(
+                    Rule::AfterPromise,
+                    statement.span(),
+                    "nothing said after a promise is ever heard".to_string(),
+                    "never runs",
+                    "a devotion stops at the first promise it keeps; move this before it or remove it",
+                );
+                promised = false;
+            } else if always_promises(statement) {
+                promised = true;
+            }
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Program { statements, .. } => self.statements(statements),
+            Ast::Block { statements, .. } => {
+                self.scopes.begin_block();
+                self.statements(statements);
+                let scope = self.scopes.end_block();
+                self.end_scope(scope);
+            }
+            Ast::VariableDecl { name, initializer, is_constant, span, .. } => {
+                self.expression(initializer);
+                if self.params.last().is_some_and(|params| params.contains(name)) {
+                    self.report(
+                        Rule::ShadowedVow,
+                        *span,
+                        format!("'{}' hides the devotion's parameter of the same name", name),
+                        "hides a parameter",
+                        "give it a name of its own, so the parameter isn't forgotten",
+                    );
+                }
+                if *is_constant && name.chars().any(char::is_lowercase) {
+                    self.report(
+                        Rule::QuietForever,
+                        *span,
+                        format!("forever '{}' should be declared out loud, as '{}'", name, name.to_uppercase()),
+                        "should be in capitals",
+                        "forevers are written in capitals, so they stand out from hearts that may change",
+                    );
+                }
+                self.declare(name, *span, false);
+            }
+            Ast::FunctionDecl { name, params, body, span, .. } => {
+                self.declare(name, *span, true);
+                self.scopes.begin_devotion(HashMap::new());
+                for (param, _, param_span) in params {
+                    self.declare(param, *param_span, true);
+                }
+                self.params.push(params.iter().map(|(param, _, _)| param.clone()).collect());
+                self.statements(body);
+                self.params.pop();
+                for scope in self.scopes.end_devotion() {
+                    self.end_scope(scope);
+                }
+            }
+            Ast::If { condition, then_branch, else_branch, .. } => {
+                self.condition(condition, "crush");
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Ast::While { condition, body, span } => {
+                self.condition(condition, "dating");
+                self.stuck(condition, body, *span);
+                self.statements(body);
+            }
+            Ast::ExpressionStmt { expr, .. } | Ast::PrintStmt { value: expr, .. } => self.expression(expr),
+            Ast::ReturnStmt { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            _ => self.expression(ast),
+        }
+    }
+
+    fn expression(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Variable { name, .. } => self.read(name),
+            Ast::Call { callee, arguments, .. } => {
+                self.read(callee);
+                arguments.iter().for_each(|a| self.expression(a));
+            }
+            Ast::Assign { value, .. } => self.expression(value),
+            Ast::Grouping { expr, .. } | Ast::Unary { operand: expr, .. } => self.expression(expr),
+            Ast::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Ast::Literal { .. } => {}
+            // Statements only show up here when nested where an expression
+            // was expected, which the parser doesn't produce
+            _ => self.statement(ast),
+        }
+    }
+
+    fn condition(&mut self, condition: &Ast, keyword: &str) {
+        self.expression(condition);
+        // A fresh optimizer doesn't know any forevers, so a condition
+        // naming one (a debug switch, say) isn't reported
+        if let Ast::Literal { value: Value::Boolean(true), .. } = optimize(condition.clone()) {
+            let hint = match keyword {
+                "crush" => "the butterflies branch can never run, so the crush isn't needed",
+                _ => "the loop only ends with a promise or goodbye; allow always-yes if that's the plan",
+            };
+            self.report(
+                Rule::AlwaysYes,
+                condition.span(),
+                format!("this {} condition always says yes", keyword),
+                "always yes",
+                hint,
+            );
+        }
+    }
+
+    /// Reports a `dating` loop whose body can't change what its condition
+    /// reads. Bodies that call devotions or make promises might end the
+    /// loop some other way, so they are left alone.
+    fn stuck(&mut self, condition: &Ast, body: &[Ast], span: Span) {
+        let mut read = Vec::new();
+        let mut escapes = false;
+        each_node(condition, &mut |node| match node {
+            Ast::Variable { name, .. } if !read.contains(name) => read.push(name.clone()),
+            Ast::Call { .. } => escapes = true,
+            _ => {}
+        });
+        for statement in body {
+            each_node(statement, &mut |node| {
+                escapes |= matches!(node, Ast::Call { .. } | Ast::ReturnStmt { .. });
+            });
+        }
+        let mut assigned = HashSet::new();
+        body.iter().for_each(|statement| collect_assigned(statement, &mut assigned));
+        if read.is_empty() || escapes || read.iter().any(|name| assigned.contains(name)) {
+            return;
+        }
+        let names: Vec<String> = read.iter().map(|name| format!("'{}'", name)).collect();
+        self.report(
+            Rule::StuckDating,
+            span,
+            format!("this dating never changes {}, so it either never starts or never ends", names.join(" or ")),
+            "stuck",
+            "match one of them to a new value inside the loop, or promise your way out of it",
+        );
+    }
+}
+
+/// Whether `statement` always ends the devotion it is in.
+fn always_promises(statement: &Ast) -> bool {
+    match statement {
+        Ast::ReturnStmt { .. } => true,
+        Ast::Block { statements, .. } => statements.iter().any(always_promises),
+        Ast::If { then_branch, else_branch: Some(else_branch), .. } => {
+            then_branch.iter().any(always_promises) && else_branch.iter().any(always_promises)
+        }
+        _ => false,
+    }
+}
+
+/// Calls `visit` on `ast` and everything inside it, not looking into
+/// devotions declared there.
+fn each_node(ast: &Ast, visit: &mut impl FnMut(&Ast)) {
+    visit(ast);
+    if !matches!(ast, Ast::FunctionDecl { .. }) {
+        ast.children().into_iter().for_each(|child| each_node(child, visit));
+    }
+}
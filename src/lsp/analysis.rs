@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use crate::builtins::Builtin;
+use crate::checker::{check, declared_types};
+use crate::diagnostic::Diagnostic;
+use crate::error::LoveError;
+use crate::lexer::{Lexer, SpannedToken, Token};
+use crate::linter::{lint, LintConfig};
+use crate::parser::ast::Ast;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scopes::Scopes;
+use crate::shared_types::Type;
+use crate::span::Span;
+
+/// What kind of name a symbol is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Heart,
+    Forever,
+    Devotion,
+    Parameter,
+}
+
+/// A name declared in a love story.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name itself is written in the declaration.
+    pub name_span: Span,
+    /// The whole declaration.
+    pub span: Span,
+    /// The declaration as it would be written, with the types that are
+    /// certain, e.g. `heart count: number`.
+    pub detail: String,
+    /// The devotion it was declared in, as an index into the symbols.
+    pub parent: Option<usize>,
+}
+
+/// What a use of a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Symbol(usize),
+    Builtin(Builtin),
+}
+
+/// Everything the server knows about one version of a document.
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub symbols: Vec<Symbol>,
+    /// Each use of a name, with the span of the name and what it refers to.
+    pub references: Vec<(Span, Target)>,
+}
+
+impl Analysis {
+    /// Lexes, parses, resolves, checks and lints `text`. Problems found
+    /// along the way become diagnostics. A story that doesn't parse is
+    /// still analysed as far as its statements that did parse, so symbols
+    /// stay available while a line is half typed.
+    pub fn new(text: &str) -> Self {
+        let mut analysis = Analysis { diagnostics: Vec::new(), symbols: Vec::new(), references: Vec::new() };
+        let tokens = match Lexer::new(text).tokenize() {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                analysis.report(&e);
+                return analysis;
+            }
+        };
+        let (mut ast, parse_errors) = Parser::new(tokens.clone()).parse_recovering();
+        for error in &parse_errors {
+            analysis.report(error);
+        }
+        match Resolver::new().resolve(&mut ast).and_then(|_| check(&ast)) {
+            Ok(()) => {}
+            Err(e) => analysis.report(&e),
+        }
+        // Lints about what is never read or never changes would blame the
+        // statements that did parse for what the broken ones do
+        if parse_errors.is_empty() {
+            analysis.diagnostics.extend(lint(&ast, &LintConfig::new()).iter().map(|lint| lint.diagnostic()));
+        }
+
+        let mut walker = Walker {
+            tokens: &tokens,
+            types: declared_types(&ast),
+            symbols: Vec::new(),
+            references: Vec::new(),
+            scopes: Scopes::new(),
+            devotions: Vec::new(),
+            pending: Vec::new(),
+        };
+        walker.statement(&ast);
+        walker.resolve_pending();
+        analysis.symbols = walker.symbols;
+        analysis.references = walker.references;
+        analysis
+    }
+
+    fn report(&mut self, error: &LoveError) {
+        self.diagnostics.extend(error.errors().into_iter().map(Diagnostic::from_error));
+    }
+
+    /// The use or declaration of a name at byte `offset`, as what it
+    /// refers to and where the name is written.
+    pub fn target_at(&self, offset: usize) -> Option<(Target, Span)> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        self.references.iter()
+            .find(|(span, _)| contains(span))
+            .map(|&(span, target)| (target, span))
+            .or_else(|| {
+                self.symbols.iter().position(|symbol| contains(&symbol.name_span))
+                    .map(|index| (Target::Symbol(index), self.symbols[index].name_span))
+            })
+    }
+
+    /// How to describe `target` to the author.
+    pub fn detail(&self, target: Target) -> String {
+        match target {
+            Target::Symbol(index) => self.symbols[index].detail.clone(),
+            Target::Builtin(builtin) => {
+                let params: Vec<(String, Type)> = builtin.params().iter()
+                    .map(|(name, t)| (name.to_string(), t.clone()))
+                    .collect();
+                signature(builtin.name(), &params, builtin.returns().as_ref())
+            }
+        }
+    }
+
+    /// The symbols a name written at byte `offset` could refer to: those
+    /// declared at the top level and those of the devotions around it.
+    pub fn visible_at(&self, offset: usize) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().filter(move |symbol| match symbol.parent {
+            None => true,
+            Some(parent) => {
+                let span = self.symbols[parent].span;
+                span.start <= offset && offset <= span.end
+            }
+        })
+    }
+}
+
+/// `devotion name(params) -> returns`.
+fn signature(name: &str, params: &[(String, Type)], returns: Option<&Type>) -> String {
+    let params: Vec<String> = params.iter().map(|(param, t)| format!("{}: {}", param, t)).collect();
+    let returns = returns.map(|t| format!(" -> {}", t)).unwrap_or_default();
+    format!("devotion {}({}){}", name, params.join(", "), returns)
+}
+
+/// Finds every declaration and use of a name, binding uses to
+/// declarations the way the resolver does.
+struct Walker<'t> {
+    tokens: &'t [SpannedToken],
+    types: HashMap<usize, Type>,
+    symbols: Vec<Symbol>,
+    references: Vec<(Span, Target)>,
+    /// The symbol each name in scope refers to.
+    scopes: Scopes<usize>,
+    /// The devotions being walked, innermost last.
+    devotions: Vec<usize>,
+    /// Uses no local was found for, to look up among the globals once
+    /// they are all known.
+    pending: Vec<(Span, String)>,
+}
+
+impl Walker<'_> {
+    /// Where `name` is written, looking from byte `from` on.
+    fn name_span(&self, name: &str, from: usize) -> Span {
+        self.tokens.iter()
+            .find(|t| t.span.start >= from && matches!(&t.token, Token::Identifier(n) if n == name))
+            .map_or(Span::new(from, from, 0, 0), |t| t.span)
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, name_span: Span, span: Span, detail: String) {
+        let index = self.symbols.len();
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            name_span,
+            span,
+            detail,
+            parent: self.devotions.last().copied(),
+        });
+        self.scopes.declare(name, index);
+    }
+
+    /// Records a use of `name` written at `start`.
+    fn use_name(&mut self, name: &str, start: Span) {
+        let span = Span { end: start.start + name.len(), ..start };
+        match self.scopes.local(name) {
+            Some(&index) => self.references.push((span, Target::Symbol(index))),
+            None => self.pending.push((span, name.to_string())),
+        }
+    }
+
+    /// Binds the uses that weren't locals to globals, or failing that to
+    /// built-in devotions.
+    fn resolve_pending(&mut self) {
+        for (span, name) in std::mem::take(&mut self.pending) {
+            // Every block and devotion has ended, so only globals are left
+            let target = match self.scopes.get(&name) {
+                Some(&index) => Target::Symbol(index),
+                None => match Builtin::ALL.into_iter().find(|b| b.name() == name) {
+                    Some(builtin) => Target::Builtin(builtin),
+                    None => continue,
+                },
+            };
+            self.references.push((span, target));
+        }
+        self.references.sort_by_key(|(span, _)| span.start);
+    }
+
+    fn statements(&mut self, statements: &[Ast]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Program { statements, .. } => self.statements(statements),
+            Ast::Block { statements, .. } => {
+                self.scopes.begin_block();
+                self.statements(statements);
+                self.scopes.end_block();
+            }
+            Ast::VariableDecl { name, initializer, is_constant, span, .. } => {
+                self.statement(initializer);
+                let (kind, keyword) = if *is_constant {
+                    (SymbolKind::Forever, "forever")
+                } else {
+                    (SymbolKind::Heart, "heart")
+                };
+                let detail = match self.types.get(&span.start) {
+                    Some(found) => format!("{} {}: {}", keyword, name, found),
+                    None => format!("{} {}", keyword, name),
+                };
+                let name_span = self.name_span(name, span.start);
+                self.declare(name, kind, name_span, *span, detail);
+            }
+            Ast::FunctionDecl { name, params, return_type, body, span, .. } => {
+                let name_span = self.name_span(name, span.start);
+                let signature_params: Vec<(String, Type)> = params.iter()
+                    .map(|(param, t, _)| (param.clone(), t.clone()))
+                    .collect();
+                let detail = signature(name, &signature_params, return_type.as_ref());
+                self.declare(name, SymbolKind::Devotion, name_span, *span, detail);
+
+                self.devotions.push(self.symbols.len() - 1);
+                self.scopes.begin_devotion(HashMap::new());
+                for (param, param_type, param_span) in params {
+                    let detail = format!("{}: {}", param, param_type);
+                    self.declare(param, SymbolKind::Parameter, *param_span, *param_span, detail);
+                }
+                self.statements(body);
+                self.scopes.end_devotion();
+                self.devotions.pop();
+            }
+            Ast::If { condition, then_branch, else_branch, .. } => {
+                self.statement(condition);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Ast::While { condition, body, .. } => {
+                self.statement(condition);
+                self.statements(body);
+            }
+            Ast::ExpressionStmt { expr, .. }
+            | Ast::PrintStmt { value: expr, .. }
+            | Ast::Grouping { expr, .. }
+            | Ast::Unary { operand: expr, .. } => self.statement(expr),
+            Ast::ReturnStmt { value, .. } => {
+                if let Some(value) = value {
+                    self.statement(value);
+                }
+            }
+            Ast::Binary { left, right, .. } => {
+                self.statement(left);
+                self.statement(right);
+            }
+            Ast::Call { callee, arguments, span, .. } => {
+                self.use_name(callee, *span);
+                self.statements(arguments);
+            }
+            Ast::Assign { name, value, span, .. } => {
+                self.statement(value);
+                self.use_name(name, *span);
+            }
+            Ast::Variable { name, span, .. } => self.use_name(name, *span),
+            Ast::Literal { .. } => {}
+        }
+    }
+}
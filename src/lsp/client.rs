@@ -0,0 +1,140 @@
+use std::io::Cursor;
+
+use serde_json::{json, Value};
+
+use super::transport::{read_message, write_message};
+use super::Server;
+
+/// Drives a `Server` in the same process, the way an editor would over
+/// stdio: every message goes through the same framing, just in memory.
+pub struct Client {
+    server: Server,
+    next_id: u64,
+    /// Notifications from the server that haven't been taken yet.
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Client { server: Server::new(), next_id: 1, notifications: Vec::new() }
+    }
+
+    /// Sends a request, returning the result of its response, or the
+    /// error the server answered with.
+    pub fn request(&mut self, method: &str, params: Value) -> Result<Value, Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut response = None;
+        for message in self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })) {
+            if message["id"] == id {
+                response = Some(message);
+            } else {
+                self.notifications.push(message);
+            }
+        }
+        let mut response = response.expect("the server answers every request");
+        match response.get_mut("error") {
+            Some(error) => Err(error.take()),
+            None => Ok(response["result"].take()),
+        }
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        let replies = self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+        self.notifications.extend(replies);
+    }
+
+    /// Takes the notifications the server has sent since last asked.
+    pub fn notifications(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Frames `message`, has the server read it, and unframes its replies.
+    fn send(&mut self, message: Value) -> Vec<Value> {
+        let mut wire = Vec::new();
+        write_message(&mut wire, &message).expect("writing to memory can't fail");
+        let body = read_message(&mut Cursor::new(wire))
+            .expect("reading from memory can't fail")
+            .expect("a message was just written");
+
+        let mut wire = Vec::new();
+        for reply in self.server.receive(&body) {
+            write_message(&mut wire, &reply).expect("writing to memory can't fail");
+        }
+        let mut wire = Cursor::new(wire);
+        let mut replies = Vec::new();
+        while let Some(body) = read_message(&mut wire).expect("reading from memory can't fail") {
+            replies.push(serde_json::from_str(&body).expect("the server sends JSON"));
+        }
+        replies
+    }
+
+    /// Runs the `initialize` handshake, returning the server's
+    /// capabilities.
+    pub fn initialize(&mut self) -> Result<Value, Value> {
+        let result = self.request("initialize", json!({ "processId": null, "rootUri": null, "capabilities": {} }))?;
+        self.notify("initialized", json!({}));
+        Ok(result["capabilities"].clone())
+    }
+
+    pub fn open(&mut self, uri: &str, text: &str) {
+        self.notify("textDocument/didOpen", json!({
+            "textDocument": { "uri": uri, "languageId": "love", "version": 1, "text": text },
+        }));
+    }
+
+    pub fn change(&mut self, uri: &str, text: &str) {
+        self.notify("textDocument/didChange", json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": text }],
+        }));
+    }
+
+    /// The diagnostics most recently published for `uri`.
+    pub fn diagnostics(&self, uri: &str) -> Vec<Value> {
+        self.notifications.iter().rev()
+            .find(|n| n["method"] == "textDocument/publishDiagnostics" && n["params"]["uri"] == uri)
+            .and_then(|n| n["params"]["diagnostics"].as_array().cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn hover(&mut self, uri: &str, line: usize, character: usize) -> Result<Value, Value> {
+        self.request("textDocument/hover", position(uri, line, character))
+    }
+
+    pub fn definition(&mut self, uri: &str, line: usize, character: usize) -> Result<Value, Value> {
+        self.request("textDocument/definition", position(uri, line, character))
+    }
+
+    pub fn completion(&mut self, uri: &str, line: usize, character: usize) -> Result<Value, Value> {
+        self.request("textDocument/completion", position(uri, line, character))
+    }
+
+    pub fn symbols(&mut self, uri: &str) -> Result<Value, Value> {
+        self.request("textDocument/documentSymbol", json!({ "textDocument": { "uri": uri } }))
+    }
+
+    /// Runs the `shutdown` and `exit` handshake.
+    pub fn shutdown(&mut self) -> Result<(), Value> {
+        self.request("shutdown", Value::Null)?;
+        self.notify("exit", Value::Null);
+        Ok(())
+    }
+
+    /// Once `exit` was sent, whether the server was shut down properly
+    /// first.
+    pub fn exited(&self) -> Option<bool> {
+        self.server.exited()
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `TextDocumentPositionParams`.
+fn position(uri: &str, line: usize, character: usize) -> Value {
+    json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+}
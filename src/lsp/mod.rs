@@ -0,0 +1,332 @@
+//! A Language Server Protocol server for love stories, so editors can show
+//! problems as they are typed, types on hover, where names are declared,
+//! completions and an outline.
+//!
+//! `serve` speaks JSON-RPC over any reader and writer; the `love-lsp`
+//! binary runs it on stdin and stdout. `Client` drives a server in the same
+//! process, for trying it out without an editor.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::builtins::Builtin;
+use crate::diagnostic::Level;
+use crate::lexer::Token;
+
+use analysis::{Analysis, SymbolKind, Target};
+use position::LineIndex;
+
+mod analysis;
+mod client;
+mod position;
+mod transport;
+
+pub use client::Client;
+
+/// JSON-RPC error codes the server answers with.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// LSP kinds for completion items and document symbols.
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+const COMPLETION_CONSTANT: u8 = 21;
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_VARIABLE: u8 = 13;
+const SYMBOL_CONSTANT: u8 = 14;
+
+/// Runs a server over `input` and `output` until the client says `exit`
+/// or `input` ends. Returns whether the client shut the server down
+/// properly first.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+    let mut server = Server::new();
+    while let Some(body) = transport::read_message(&mut input)? {
+        for reply in server.receive(&body) {
+            transport::write_message(&mut output, &reply)?;
+        }
+        if let Some(clean) = server.exited() {
+            return Ok(clean);
+        }
+    }
+    Ok(false)
+}
+
+/// An open document and what is known about its text.
+struct Document {
+    lines: LineIndex,
+    analysis: Analysis,
+}
+
+impl Document {
+    fn new(text: &str) -> Self {
+        Document { lines: LineIndex::new(text), analysis: Analysis::new(text) }
+    }
+}
+
+/// A request that can't be answered, as sent back to the client.
+struct Failure {
+    code: i64,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Failure { code, message: message.into() }
+    }
+}
+
+/// The state of a conversation with one client: the documents it has open
+/// and how far along the shutdown handshake is.
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server { documents: HashMap::new(), shut_down: false, exited: false }
+    }
+
+    /// Once the client has said `exit`, whether it said `shutdown` before.
+    pub fn exited(&self) -> Option<bool> {
+        self.exited.then_some(self.shut_down)
+    }
+
+    /// Handles the body of one message, returning the messages to send
+    /// back: the response to a request, and any notifications.
+    pub fn receive(&mut self, body: &str) -> Vec<Value> {
+        match serde_json::from_str(body) {
+            Ok(message) => self.handle(&message),
+            Err(e) => vec![failure(Value::Null, Failure::new(PARSE_ERROR, e.to_string()))],
+        }
+    }
+
+    /// Handles one parsed message, like `receive`.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params);
+        };
+        let result = if self.shut_down {
+            Err(Failure::new(INVALID_REQUEST, "the server is shutting down"))
+        } else {
+            self.request(method, params)
+        };
+        match result {
+            Ok(result) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            Err(e) => vec![failure(id, e)],
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+        match method {
+            "exit" => {
+                self.exited = true;
+                Vec::new()
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Document::new(text));
+                vec![self.publish(&uri)]
+            }
+            "textDocument/didChange" => {
+                // The server asks for whole documents, so the last change
+                // is the new text
+                let Some(text) = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.clone(), Document::new(text));
+                vec![self.publish(&uri)]
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![self.publish(&uri)]
+            }
+            // Including 'initialized', and anything this server doesn't
+            // know, which notifications allow ignoring
+            _ => Vec::new(),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, Failure> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "love-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/documentSymbol" => self.symbols(params),
+            _ => Err(Failure::new(METHOD_NOT_FOUND, format!("love-lsp doesn't know '{}'", method))),
+        }
+    }
+
+    /// The document a request is about, and the byte offset of the
+    /// position it names, if it names one.
+    fn document<'a>(&'a self, params: &'a Value) -> Result<(&'a str, &'a Document, usize), Failure> {
+        let uri = params["textDocument"]["uri"].as_str()
+            .ok_or_else(|| Failure::new(INVALID_PARAMS, "missing textDocument.uri"))?;
+        let document = self.documents.get(uri)
+            .ok_or_else(|| Failure::new(INVALID_PARAMS, format!("{} isn't open", uri)))?;
+        let position = &params["position"];
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        let column = position["character"].as_u64().unwrap_or_default() as usize;
+        Ok((uri, document, document.lines.offset(line, column)))
+    }
+
+    /// The type of the name under the cursor, or its signature if it is a
+    /// devotion.
+    fn hover(&self, params: &Value) -> Result<Value, Failure> {
+        let (_, document, offset) = self.document(params)?;
+        let Some((target, span)) = document.analysis.target_at(offset) else {
+            return Ok(Value::Null);
+        };
+        let mut value = format!("```love\n{}\n```", document.analysis.detail(target));
+        if let Target::Builtin(_) = target {
+            value.push_str("\n\nBuilt into the language.");
+        }
+        Ok(json!({
+            "contents": { "kind": "markdown", "value": value },
+            "range": document.lines.range(span),
+        }))
+    }
+
+    /// Where the name under the cursor is declared. Built-in devotions
+    /// aren't declared anywhere.
+    fn definition(&self, params: &Value) -> Result<Value, Failure> {
+        let (uri, document, offset) = self.document(params)?;
+        match document.analysis.target_at(offset) {
+            Some((Target::Symbol(index), _)) => Ok(json!({
+                "uri": uri,
+                "range": document.lines.range(document.analysis.symbols[index].name_span),
+            })),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// Every reserved word, the built-in devotions and the names visible
+    /// at the cursor. Editors filter them by what has been typed.
+    fn completion(&self, params: &Value) -> Result<Value, Failure> {
+        let (_, document, offset) = self.document(params)?;
+        let mut items: Vec<Value> = Token::WORDS.iter()
+            .map(|word| json!({ "label": word.to_string(), "kind": COMPLETION_KEYWORD }))
+            .collect();
+        for builtin in Builtin::ALL {
+            items.push(json!({
+                "label": builtin.name(),
+                "kind": COMPLETION_FUNCTION,
+                "detail": document.analysis.detail(Target::Builtin(builtin)),
+            }));
+        }
+        let mut seen = Vec::new();
+        for symbol in document.analysis.visible_at(offset) {
+            if seen.contains(&symbol.name) {
+                continue;
+            }
+            seen.push(symbol.name.clone());
+            let kind = match symbol.kind {
+                SymbolKind::Devotion => COMPLETION_FUNCTION,
+                SymbolKind::Forever => COMPLETION_CONSTANT,
+                SymbolKind::Heart | SymbolKind::Parameter => COMPLETION_VARIABLE,
+            };
+            items.push(json!({ "label": symbol.name, "kind": kind, "detail": symbol.detail }));
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// An outline of the document: its devotions, hearts and forevers, with
+    /// what a devotion declares nested under it.
+    fn symbols(&self, params: &Value) -> Result<Value, Failure> {
+        let (_, document, _) = self.document(params)?;
+        Ok(Value::Array(self.outline(document, None)))
+    }
+
+    fn outline(&self, document: &Document, parent: Option<usize>) -> Vec<Value> {
+        let symbols = &document.analysis.symbols;
+        (0..symbols.len())
+            .filter(|&index| symbols[index].parent == parent)
+            .filter_map(|index| {
+                let symbol = &symbols[index];
+                let kind = match symbol.kind {
+                    SymbolKind::Devotion => SYMBOL_FUNCTION,
+                    SymbolKind::Forever => SYMBOL_CONSTANT,
+                    SymbolKind::Heart => SYMBOL_VARIABLE,
+                    SymbolKind::Parameter => return None,
+                };
+                Some(json!({
+                    "name": symbol.name,
+                    "detail": symbol.detail,
+                    "kind": kind,
+                    "range": document.lines.range(symbol.span),
+                    "selectionRange": document.lines.range(symbol.name_span),
+                    "children": self.outline(document, Some(index)),
+                }))
+            })
+            .collect()
+    }
+
+    /// The diagnostics of the document at `uri`, as a notification. A
+    /// closed document has none.
+    fn publish(&self, uri: &str) -> Value {
+        let diagnostics: Vec<Value> = match self.documents.get(uri) {
+            Some(document) => document.analysis.diagnostics.iter()
+                .map(|diagnostic| {
+                    let mut message = diagnostic.message.clone();
+                    if let Some(hint) = &diagnostic.hint {
+                        message.push_str(&format!("\nhint: {}", hint));
+                    }
+                    let span = diagnostic.span.unwrap_or_default();
+                    json!({
+                        "range": document.lines.range(span),
+                        "severity": if diagnostic.level == Level::Error { 1 } else { 2 },
+                        "code": diagnostic.code,
+                        "source": "love",
+                        "message": message,
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn failure(id: Value, failure: Failure) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": failure.code, "message": failure.message },
+    })
+}
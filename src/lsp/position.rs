@@ -0,0 +1,54 @@
+use serde_json::{json, Value};
+
+use crate::span::Span;
+
+/// Converts between byte offsets and the positions editors use: 0-based
+/// lines and 0-based columns counted in UTF-16 code units.
+pub struct LineIndex {
+    text: String,
+    /// Byte offset where each line starts.
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex { text: text.to_string(), starts }
+    }
+
+    /// The line and UTF-16 column of the byte at `offset`.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.starts[line];
+        let column = self.text[start..offset].chars().map(char::len_utf16).sum();
+        (line, column)
+    }
+
+    /// The byte offset of `line` and UTF-16 `column`, clamped to the text.
+    pub fn offset(&self, line: usize, column: usize) -> usize {
+        let Some(&start) = self.starts.get(line) else {
+            return self.text.len();
+        };
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= column || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+
+    /// `span` as an LSP `Range`.
+    pub fn range(&self, span: Span) -> Value {
+        let (start_line, start_column) = self.position(span.start);
+        let (end_line, end_column) = self.position(span.end);
+        json!({
+            "start": { "line": start_line, "character": start_column },
+            "end": { "line": end_line, "character": end_column },
+        })
+    }
+}
@@ -1,45 +1,134 @@
 use rustyline::Editor;
 use colored::*;
-use std::io::{self};
+use std::io::{self, IsTerminal};
 use std::env;
+use std::panic;
+use std::process::ExitCode;
+use std::thread;
 
-mod shared_types;
-mod lexer;
-mod parser;
-mod interpreter;
-mod error;
-mod fun;
-mod runner;
-
-use crate::runner::Runner;
-use crate::shared_types::Value;
-use crate::error::LoveError;
-use crate::lexer::Lexer;
-use crate::parser::Parser;
-use crate::fun::*;
-
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    
-    match args.len() {
-        1 => run_repl(),
-        
-        2 => run_file(&args[1]),
-    
-        _ => {
-            println!("{}", create_love_border(
-                "Usage: love-language [script.love]"
-            ).bright_red());
-            Ok(())
+use love_language::{interpreter, lexer};
+use love_language::runner::Runner;
+use love_language::shared_types::Value;
+use love_language::diagnostic::render_error;
+use love_language::error::LoveError;
+use love_language::lexer::Lexer;
+use love_language::parser::Parser;
+use love_language::fun::*;
+
+mod cli;
+
+use cli::Command;
+
+/// The status for a story that is fine to run but failed a check: `fmt
+/// --check` found it unformatted, or `lint` found a denied lint. Stories
+/// can't fail with it on their own, apart from saying `goodbye(7)`.
+const CHECK_FAILED: u8 = 7;
+
+/// Stack for the thread everything runs on. Each nested devotion call takes
+/// tens of kilobytes of it in debug builds, far more than the main thread
+/// has for the default call depth.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn main() -> ExitCode {
+    let love = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_cli)
+        .expect("failed to start the love thread");
+    love.join().unwrap_or_else(|panic| panic::resume_unwind(panic))
+}
+
+fn run_cli() -> ExitCode {
+    let (command, options) = match cli::parse(env::args().skip(1).collect()) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("{}", create_love_border(&format!("💔 {}", error.message)).bright_red());
+            eprintln!("{}", error.usage);
+            return ExitCode::from(2);
+        }
+    };
+
+    let plain = options.plain.unwrap_or_else(|| !io::stdout().is_terminal());
+    match options.plain {
+        Some(false) => colored::control::set_override(true),
+        _ if plain => colored::control::set_override(false),
+        _ => {}
+    }
+
+    let seed = match options.seed {
+        Some(seed) => Some(seed),
+        None => match env::var("LOVE_SEED") {
+            Ok(seed) => match seed.parse() {
+                Ok(seed) => Some(seed),
+                Err(_) => {
+                    eprintln!("{}", create_love_border("💔 LOVE_SEED must be a whole number").bright_red());
+                    return ExitCode::from(2);
+                }
+            },
+            Err(_) => None,
+        },
+    };
+    if options.no_flavor {
+        set_flavor_enabled(false);
+    } else if let Some(seed) = seed {
+        seed_flavor(seed);
+    }
+
+    let result = match command {
+        Command::Help(usage) => {
+            println!("{}", usage);
+            return ExitCode::SUCCESS;
         }
+        Command::Repl => return run_repl(),
+        Command::Run { source, engine, optimize, args } => {
+            let mut runner = Runner::with_engine(engine, Box::new(io::stdout()));
+            runner.set_plain(plain);
+            runner.set_optimize(optimize);
+            runner.set_script_args(args);
+            // Stories run from the command line belong to whoever runs them
+            runner.set_allow_env(true);
+            runner.run(&source)
+        }
+        Command::Check { source } => new_runner(plain).check(&source),
+        Command::Tokens { source } => new_runner(plain).print_tokens(&source),
+        Command::Ast { source, optimize } => {
+            let mut runner = new_runner(plain);
+            runner.set_optimize(optimize);
+            runner.print_ast(&source)
+        }
+        Command::Fmt { source, check: false } => new_runner(plain).format(&source),
+        Command::Fmt { source, check: true } => match new_runner(plain).check_format(&source) {
+            Ok(true) => Ok(()),
+            Ok(false) => return ExitCode::from(CHECK_FAILED),
+            Err(e) => Err(e),
+        },
+        Command::Highlight { source } => new_runner(plain).print_highlights(&source),
+        Command::Lint { source, config } => match new_runner(plain).lint(&source, &config) {
+            Ok(true) => Ok(()),
+            Ok(false) => return ExitCode::from(CHECK_FAILED),
+            Err(e) => Err(e),
+        },
+    };
+
+    // The runner has already reported any error as a diagnostic
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(e.exit_code()),
     }
 }
 
-fn run_repl() -> io::Result<()> {
+/// A runner for the commands that don't run the story.
+fn new_runner(plain: bool) -> Runner {
+    let mut runner = Runner::new();
+    runner.set_plain(plain);
+    runner
+}
+
+fn run_repl() -> ExitCode {
     print_welcome_message();
 
     let mut rl = Editor::<()>::new();
     let mut interpreter = interpreter::Interpreter::new();
+    interpreter.set_allow_env(true);
     let mut current_line = String::new();
     let mut brace_count = 0;
 
@@ -71,7 +160,7 @@ fn run_repl() -> io::Result<()> {
                 if brace_count == 0 && !trimmed_line.is_empty() && 
                    !trimmed_line.ends_with(';') && !trimmed_line.ends_with('{') && 
                    !trimmed_line.ends_with('}') && !current_line.contains("devotion") {
-                    println!("{} {}", "💔".bright_red(), format!("{}\nMissing semicolon at end of statement", get_random_error_message()));
+                    println!("{}", heartbreak("Missing semicolon at end of statement"));
                     current_line.clear();
                     continue;
                 }
@@ -86,46 +175,38 @@ fn run_repl() -> io::Result<()> {
                                     match value {
                                         Value::Null => (),
                                         _ => {
-                                            println!("{} {}", get_random_emoji(), get_random_success_message().bright_green());
-                                            println!("{} {:?}", get_random_emoji(), value)
+                                            if let Some(message) = get_random_success_message() {
+                                                println!("{} {}", get_random_emoji(), message.bright_green());
+                                            }
+                                            println!("{} {}", get_random_emoji(), value)
                                         },
                                     }
                                 },
-                                Err(e) => println!("{} {}", "💔".bright_red(), format_error(&e).bright_red()),
+                                Err(LoveError::Goodbye { status }) => {
+                                    println!("{}", create_love_border("Goodbye! Our love story ends here...").bright_red());
+                                    return ExitCode::from(status);
+                                }
+                                Err(e) => println!("{}", format_error(&e, &current_line)),
                             }
                         }
-                        Err(e) => println!("{} {}", "💔".bright_red(), format_error(&e).bright_red()),
+                        Err(e) => println!("{}", format_error(&e, &current_line)),
                     }
                     
                     current_line.clear();
                 } else if brace_count < 0 {
-                    println!("{} {}", "💔".bright_red(), 
-                        format!("{}\nUnmatched closing brace", get_random_error_message()));
+                    println!("{}", heartbreak("Unmatched closing brace"));
                     current_line.clear();
                     brace_count = 0;
                 }
             }
             Err(err) => {
-                println!("{} {}", "💔".bright_red(), format!("{}\n{}", 
-                    get_random_error_message(), err.to_string()).bright_red());
+                println!("{}", heartbreak(&err.to_string()).bright_red());
                 break;
             }
         }
     }
 
-    Ok(())
-}
-
-fn run_file(path: &str) -> io::Result<()> {
-    let mut runner = Runner::new();
-    
-    if let Err(e) = runner.run_file(path) {
-        println!("{}", create_love_border(
-            &format!("💔 Our love story encountered an error:\n{}", e)
-        ).bright_red());
-    }
-    
-    Ok(())
+    ExitCode::SUCCESS
 }
 
 fn print_welcome_message() {
@@ -189,11 +270,15 @@ fn count_braces(line: &str) -> i32 {
     count
 }
 
-fn format_error(error: &LoveError) -> String {
-    match error {
-        LoveError::Lexer(msg) => format!("{}\n{}", get_random_error_message(), msg),
-        LoveError::Parser(msg) => format!("{}\n{}", get_random_error_message(), msg),
-        LoveError::Runtime(msg) => format!("{}\n{}", get_random_error_message(), msg),
-        LoveError::Type(msg) => format!("{}\n{}", get_random_error_message(), msg),
+fn format_error(error: &LoveError, source: &str) -> String {
+    render_error(error, source, "<repl>", get_random_error_message())
+}
+
+/// A REPL complaint about `problem`, after a sad flavor message if there is
+/// one.
+fn heartbreak(problem: &str) -> String {
+    match get_random_error_message() {
+        Some(message) => format!("{} {}\n{}", "💔".bright_red(), message, problem),
+        None => format!("{} {}", "💔".bright_red(), problem),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::interpreter::operators;
+use crate::parser::ast::Ast;
+use crate::scopes::Scopes;
+use crate::shared_types::{BinaryOp, Value};
+use crate::span::Span;
+
+/// Simplifies a parsed love story without changing what it does: constant
+/// arithmetic and comparisons are folded, `forever` constants bound to
+/// literals are inlined where they are used, and `crush` branches that can
+/// never run are dropped.
+///
+/// Anything that would fail at runtime (`1 split 0`, `"a" kiss 2`) is left
+/// alone so the error still points at the right place when it happens.
+pub fn optimize(ast: Ast) -> Ast {
+    let mut assigned = HashSet::new();
+    collect_assigned(&ast, &mut assigned);
+    let mut optimizer = Optimizer { assigned, scopes: Scopes::new(), conditional: 0 };
+    optimizer.node(ast)
+}
+
+/// Names that are ever reassigned. A `forever` is only inlined if nothing
+/// ever matches it to something else.
+pub(crate) fn collect_assigned(ast: &Ast, names: &mut HashSet<String>) {
+    ast.walk(&mut |node| {
+        if let Ast::Assign { name, .. } = node {
+            names.insert(name.clone());
+        }
+    });
+}
+
+struct Optimizer {
+    assigned: HashSet<String>,
+    /// Every name in scope, with the value of those that are inlinable
+    /// constants.
+    scopes: Scopes<Option<Value>>,
+    /// How many `crush`/`dating` bodies deep we are. Constants declared in
+    /// them might never exist, so they aren't inlined.
+    conditional: usize,
+}
+
+impl Optimizer {
+    fn declare(&mut self, name: &str, constant: Option<Value>) {
+        let constant = constant.filter(|_| self.conditional == 0);
+        self.scopes.declare(name, constant);
+    }
+
+    /// The literal value `name` always has here, if it is such a constant.
+    fn constant(&self, name: &str) -> Option<Value> {
+        self.scopes.get(name).cloned().flatten()
+    }
+
+    fn node(&mut self, ast: Ast) -> Ast {
+        match ast {
+            Ast::Program { statements, span } => Ast::Program { statements: self.statements(statements), span },
+            Ast::Block { statements, span } => {
+                self.scopes.begin_block();
+                let statements = self.statements(statements);
+                self.scopes.end_block();
+                Ast::Block { statements, span }
+            }
+            Ast::VariableDecl { name, initializer, is_constant, slot, span } => {
+                let initializer = self.node(*initializer);
+                let constant = match &initializer {
+                    Ast::Literal { value, .. } if is_constant && !self.assigned.contains(&name) => Some(value.clone()),
+                    _ => None,
+                };
+                self.declare(&name, constant);
+                Ast::VariableDecl { name, initializer: Box::new(initializer), is_constant, slot, span }
+            }
+            Ast::FunctionDecl { name, params, return_type, body, slot, span } => {
+                self.declare(&name, None);
+                self.scopes.begin_devotion(params.iter().map(|(param, _, _)| (param.clone(), None)).collect());
+                let conditional = std::mem::take(&mut self.conditional);
+                let body = self.statements(Rc::unwrap_or_clone(body));
+                self.conditional = conditional;
+                self.scopes.end_devotion();
+                Ast::FunctionDecl { name, params, return_type, body: Rc::new(body), slot, span }
+            }
+            Ast::Call { callee, arguments, slot, span } => Ast::Call {
+                callee,
+                arguments: arguments.into_iter().map(|a| self.node(a)).collect(),
+                slot,
+                span,
+            },
+            Ast::If { condition, then_branch, else_branch, span } => Ast::If {
+                condition: Box::new(self.node(*condition)),
+                then_branch: self.branch(then_branch),
+                else_branch: else_branch.map(|b| self.branch(b)),
+                span,
+            },
+            Ast::While { condition, body, span } => Ast::While {
+                condition: Box::new(self.node(*condition)),
+                body: self.branch(body),
+                span,
+            },
+            Ast::ExpressionStmt { expr, span } => Ast::ExpressionStmt { expr: Box::new(self.node(*expr)), span },
+            Ast::PrintStmt { value, span } => Ast::PrintStmt { value: Box::new(self.node(*value)), span },
+            Ast::ReturnStmt { value, span } => Ast::ReturnStmt {
+                value: value.map(|v| Box::new(self.node(*v))),
+                span,
+            },
+            Ast::Binary { left, operator, right, span } => {
+                let left = self.node(*left);
+                let right = self.node(*right);
+                fold_binary(left, operator, right, span)
+            }
+            Ast::Unary { operator, operand, span } => match self.node(*operand) {
+                Ast::Literal { value, span: operand_span } => match operators::unary(&operator, value.clone()) {
+                    Ok(folded) => Ast::Literal { value: folded, span },
+                    Err(_) => Ast::Unary { operator, operand: Box::new(Ast::Literal { value, span: operand_span }), span },
+                },
+                operand => Ast::Unary { operator, operand: Box::new(operand), span },
+            },
+            Ast::Assign { name, value, slot, span } => {
+                Ast::Assign { name, value: Box::new(self.node(*value)), slot, span }
+            }
+            Ast::Variable { name, slot, span } => match self.constant(&name) {
+                Some(value) => Ast::Literal { value, span },
+                None => Ast::Variable { name, slot, span },
+            },
+            Ast::Grouping { expr, span } => match self.node(*expr) {
+                literal @ Ast::Literal { .. } => literal,
+                expr => Ast::Grouping { expr: Box::new(expr), span },
+            },
+            literal @ Ast::Literal { .. } => literal,
+        }
+    }
+
+    /// Optimizes the statements of a `crush` or `dating` body. They run in
+    /// the enclosing scope, so names they declare stay visible afterwards.
+    fn branch(&mut self, statements: Vec<Ast>) -> Vec<Ast> {
+        self.conditional += 1;
+        let statements = self.statements(statements);
+        self.conditional -= 1;
+        statements
+    }
+
+    /// Optimizes a statement list, splicing in the branch a `crush` with a
+    /// constant condition always takes and dropping loops that never run.
+    fn statements(&mut self, statements: Vec<Ast>) -> Vec<Ast> {
+        let count = statements.len();
+        let mut result = Vec::with_capacity(count);
+        for (i, statement) in statements.into_iter().enumerate() {
+            let statement = self.node(statement);
+            let removed = statement.span();
+            let taken = match statement {
+                Ast::If { condition, then_branch, else_branch, span } => match *condition {
+                    Ast::Literal { value: Value::Boolean(true), .. } => then_branch,
+                    Ast::Literal { value: Value::Boolean(false), .. } => else_branch.unwrap_or_default(),
+                    condition => {
+                        result.push(Ast::If { condition: Box::new(condition), then_branch, else_branch, span });
+                        continue;
+                    }
+                },
+                Ast::While { condition, .. }
+                    if matches!(*condition, Ast::Literal { value: Value::Boolean(false), .. }) => Vec::new(),
+                other => {
+                    result.push(other);
+                    continue;
+                }
+            };
+            // The last statement's value is what a devotion gives back when
+            // it makes no promise, so a branch that vanished still has to
+            // leave 'lonely' behind
+            if taken.is_empty() && i + 1 == count {
+                result.push(Ast::ExpressionStmt {
+                    expr: Box::new(Ast::Literal { value: Value::Null, span: removed }),
+                    span: removed,
+                });
+            }
+            result.extend(taken);
+        }
+        result
+    }
+}
+
+/// Folds `left operator right` when both sides are literals and the
+/// operation can't fail, and short-circuits 'and'/'or' on a literal left
+/// side.
+fn fold_binary(left: Ast, operator: BinaryOp, right: Ast, span: Span) -> Ast {
+    match (&left, &operator) {
+        (Ast::Literal { value: Value::Boolean(false), .. }, BinaryOp::And)
+        | (Ast::Literal { value: Value::Boolean(true), .. }, BinaryOp::Or) => {
+            return Ast::Literal { value: Value::Boolean(operator == BinaryOp::Or), span };
+        }
+        _ => {}
+    }
+    if let (Ast::Literal { value: l, .. }, Ast::Literal { value: r, .. }) = (&left, &right) {
+        if let Ok(value) = operators::binary(l.clone(), &operator, r.clone()) {
+            return Ast::Literal { value, span };
+        }
+    }
+    Ast::Binary { left: Box::new(left), operator, right: Box::new(right), span }
+}
@@ -1,65 +1,252 @@
 use crate::shared_types::{Value, BinaryOp, Type};
+use crate::span::Span;
+use std::fmt;
+use std::rc::Rc;
+
+/// Where the resolver found a local name at runtime: `depth` scopes out
+/// from the innermost one, at `index` within that scope. Names without a
+/// slot are globals, looked up by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ast {
-    Program(Vec<Ast>),
+    Program {
+        statements: Vec<Ast>,
+        span: Span,
+    },
     
     VariableDecl {
         name: String,
         initializer: Box<Ast>,
         is_constant: bool,
+        slot: Option<Slot>,
+        span: Span,
     },
     
     FunctionDecl {
         name: String,
-        params: Vec<(String, Type)>,
+        /// Each parameter with its type and the span of its name.
+        params: Vec<(String, Type, Span)>,
         return_type: Option<Type>,
-        body: Vec<Ast>,
+        /// Shared with the devotion values made from this declaration, so
+        /// declaring and calling a devotion never copies its body.
+        body: Rc<Vec<Ast>>,
+        slot: Option<Slot>,
+        span: Span,
     },
     
     Call {
         callee: String,
         arguments: Vec<Ast>,
+        slot: Option<Slot>,
+        span: Span,
     },
     
     If {
         condition: Box<Ast>,
         then_branch: Vec<Ast>,
         else_branch: Option<Vec<Ast>>,
+        span: Span,
     },
     
     While {
         condition: Box<Ast>,
         body: Vec<Ast>,
+        span: Span,
     },
     
-    Block(Vec<Ast>),
+    Block {
+        statements: Vec<Ast>,
+        span: Span,
+    },
     
-    ExpressionStmt(Box<Ast>),
+    ExpressionStmt {
+        expr: Box<Ast>,
+        span: Span,
+    },
     
-    PrintStmt(Box<Ast>),
+    PrintStmt {
+        value: Box<Ast>,
+        span: Span,
+    },
     
-    ReturnStmt(Option<Box<Ast>>),
+    ReturnStmt {
+        value: Option<Box<Ast>>,
+        span: Span,
+    },
     
     Binary {
         left: Box<Ast>,
         operator: BinaryOp,
         right: Box<Ast>,
+        span: Span,
     },
     
     Unary {
         operator: BinaryOp,
         operand: Box<Ast>,
+        span: Span,
     },
     
     Assign {
         name: String,
         value: Box<Ast>,
+        slot: Option<Slot>,
+        span: Span,
     },
     
-    Variable(String),
+    Variable {
+        name: String,
+        slot: Option<Slot>,
+        span: Span,
+    },
     
-    Literal(Value),
+    Literal {
+        value: Value,
+        span: Span,
+    },
     
-    Grouping(Box<Ast>),
-}
\ No newline at end of file
+    Grouping {
+        expr: Box<Ast>,
+        span: Span,
+    },
+}
+
+impl Ast {
+    /// The source region this node was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Ast::Program { span, .. }
+            | Ast::VariableDecl { span, .. }
+            | Ast::FunctionDecl { span, .. }
+            | Ast::Call { span, .. }
+            | Ast::If { span, .. }
+            | Ast::While { span, .. }
+            | Ast::Block { span, .. }
+            | Ast::ExpressionStmt { span, .. }
+            | Ast::PrintStmt { span, .. }
+            | Ast::ReturnStmt { span, .. }
+            | Ast::Binary { span, .. }
+            | Ast::Unary { span, .. }
+            | Ast::Assign { span, .. }
+            | Ast::Variable { span, .. }
+            | Ast::Literal { span, .. }
+            | Ast::Grouping { span, .. } => *span,
+        }
+    }
+
+    /// The nodes directly inside this one, in source order. The body of a
+    /// devotion counts as inside its declaration.
+    pub fn children(&self) -> Vec<&Ast> {
+        match self {
+            Ast::Program { statements, .. } | Ast::Block { statements, .. } => statements.iter().collect(),
+            Ast::FunctionDecl { body, .. } => body.iter().collect(),
+            Ast::Call { arguments, .. } => arguments.iter().collect(),
+            Ast::If { condition, then_branch, else_branch, .. } => std::iter::once(&**condition)
+                .chain(then_branch)
+                .chain(else_branch.iter().flatten())
+                .collect(),
+            Ast::While { condition, body, .. } => std::iter::once(&**condition).chain(body).collect(),
+            Ast::VariableDecl { initializer: expr, .. }
+            | Ast::ExpressionStmt { expr, .. }
+            | Ast::PrintStmt { value: expr, .. }
+            | Ast::Unary { operand: expr, .. }
+            | Ast::Assign { value: expr, .. }
+            | Ast::Grouping { expr, .. } => vec![expr],
+            Ast::ReturnStmt { value, .. } => value.iter().map(|value| &**value).collect(),
+            Ast::Binary { left, right, .. } => vec![left, right],
+            Ast::Variable { .. } | Ast::Literal { .. } => Vec::new(),
+        }
+    }
+
+    /// Calls `visit` on this node and everything inside it, parents before
+    /// their children, devotion bodies included.
+    pub fn walk(&self, visit: &mut impl FnMut(&Ast)) {
+        visit(self);
+        for child in self.children() {
+            child.walk(visit);
+        }
+    }
+}
+
+/// Prints the tree one node per line, children indented under their
+/// parent, each tagged with where it starts in the source.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+impl Ast {
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let span = self.span();
+        let label = match self {
+            Ast::Program { .. } => "Program".to_string(),
+            Ast::VariableDecl { name, is_constant: true, .. } => format!("VariableDecl forever {}", name),
+            Ast::VariableDecl { name, .. } => format!("VariableDecl heart {}", name),
+            Ast::FunctionDecl { name, params, return_type, .. } => {
+                let params: Vec<String> = params.iter().map(|(p, t, _)| format!("{}: {}", p, t)).collect();
+                let returns = return_type.as_ref().map(|t| format!(" -> {}", t)).unwrap_or_default();
+                format!("FunctionDecl {}({}){}", name, params.join(", "), returns)
+            }
+            Ast::Call { callee, .. } => format!("Call {}", callee),
+            Ast::If { .. } => "If".to_string(),
+            Ast::While { .. } => "While".to_string(),
+            Ast::Block { .. } => "Block".to_string(),
+            Ast::ExpressionStmt { .. } => "ExpressionStmt".to_string(),
+            Ast::PrintStmt { .. } => "PrintStmt".to_string(),
+            Ast::ReturnStmt { .. } => "ReturnStmt".to_string(),
+            Ast::Binary { operator, .. } => format!("Binary {}", operator),
+            Ast::Unary { operator, .. } => format!("Unary {}", operator),
+            Ast::Assign { name, .. } => format!("Assign {}", name),
+            Ast::Variable { name, .. } => format!("Variable {}", name),
+            Ast::Literal { value: Value::Text(text), .. } => format!("Literal {:?}", text),
+            Ast::Literal { value, .. } => format!("Literal {}", value),
+            Ast::Grouping { .. } => "Grouping".to_string(),
+        };
+        writeln!(f, "{}{} @{}:{}", "  ".repeat(depth), label, span.line, span.column)?;
+
+        let section = |f: &mut fmt::Formatter<'_>, name: &str, nodes: &[Ast]| -> fmt::Result {
+            writeln!(f, "{}{}:", "  ".repeat(depth + 1), name)?;
+            nodes.iter().try_for_each(|node| node.write_tree(f, depth + 2))
+        };
+        match self {
+            Ast::Program { statements, .. } | Ast::Block { statements, .. } => {
+                statements.iter().try_for_each(|s| s.write_tree(f, depth + 1))
+            }
+            Ast::FunctionDecl { body, .. } => body.iter().try_for_each(|s| s.write_tree(f, depth + 1)),
+            Ast::Call { arguments, .. } => arguments.iter().try_for_each(|a| a.write_tree(f, depth + 1)),
+            Ast::If { condition, then_branch, else_branch, .. } => {
+                condition.write_tree(f, depth + 1)?;
+                section(f, "then", then_branch)?;
+                match else_branch {
+                    Some(else_branch) => section(f, "else", else_branch),
+                    None => Ok(()),
+                }
+            }
+            Ast::While { condition, body, .. } => {
+                condition.write_tree(f, depth + 1)?;
+                section(f, "body", body)
+            }
+            Ast::VariableDecl { initializer: expr, .. }
+            | Ast::ExpressionStmt { expr, .. }
+            | Ast::PrintStmt { value: expr, .. }
+            | Ast::Unary { operand: expr, .. }
+            | Ast::Assign { value: expr, .. }
+            | Ast::Grouping { expr, .. } => expr.write_tree(f, depth + 1),
+            Ast::ReturnStmt { value, .. } => match value {
+                Some(value) => value.write_tree(f, depth + 1),
+                None => Ok(()),
+            },
+            Ast::Binary { left, right, .. } => {
+                left.write_tree(f, depth + 1)?;
+                right.write_tree(f, depth + 1)
+            }
+            Ast::Variable { .. } | Ast::Literal { .. } => Ok(()),
+        }
+    }
+}
@@ -1,30 +1,123 @@
 // parser/mod.rs
 use crate::error::LoveError;
-use crate::lexer::Token;
+use crate::lexer::{SpannedToken, Token};
 use crate::shared_types::{BinaryOp, Type, Value};
+use crate::span::Span;
+use std::rc::Rc;
 use ast::Ast;
 
 pub mod ast;
 
 pub struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
     current: usize,
+    errors: Vec<LoveError>,
+    block_depth: usize,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
         Parser {
             tokens,
             current: 0,
+            errors: Vec::new(),
+            block_depth: 0,
         }
     }
 
+    /// Parses the whole program. If anything goes wrong, every error found
+    /// is returned: a single error as-is, several as `LoveError::Multiple`.
     pub fn parse(&mut self) -> Result<Ast, LoveError> {
+        let (ast, mut errors) = self.parse_recovering();
+        match errors.len() {
+            0 => Ok(ast),
+            1 => Err(errors.remove(0)),
+            _ => Err(LoveError::Multiple { errors }),
+        }
+    }
+
+    /// Parses as much of the program as possible, skipping past broken
+    /// statements. Returns the statements that did parse alongside every
+    /// error encountered, in source order.
+    pub fn parse_recovering(&mut self) -> (Ast, Vec<LoveError>) {
+        let start = self.peek_span();
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize();
+                }
+            }
+        }
+        let program = Ast::Program {
+            statements,
+            span: start.to(self.previous_span()),
+        };
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    /// Panic-mode recovery: skips tokens until a likely statement boundary,
+    /// which is just after a ';' or '}', or just before a statement keyword.
+    /// Inside a block, stops before the closing '}' so the block can finish.
+    fn synchronize(&mut self) {
+        if self.block_depth > 0 && self.check(&Token::RBrace) {
+            return;
+        }
+        // A statement that broke because its ';' is missing leaves the next
+        // statement's keyword up next, and that statement may be fine.
+        // Every statement consumes its keyword before it can fail, so
+        // stopping here always makes progress.
+        if self.at_statement_start() {
+            return;
+        }
+        self.skip_token();
+
+        while !self.is_at_end() {
+            if matches!(self.previous(), Some(Token::Semicolon) | Some(Token::RBrace)) {
+                return;
+            }
+            match self.peek() {
+                _ if self.at_statement_start() => return,
+                Some(Token::RBrace) if self.block_depth > 0 => return,
+                _ => self.skip_token(),
+            }
+        }
+    }
+
+    /// Whether the next token can only start a statement.
+    fn at_statement_start(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Heart)
+                | Some(Token::Forever)
+                | Some(Token::Devotion)
+                | Some(Token::Crush)
+                | Some(Token::Dating)
+                | Some(Token::Promise)
+                | Some(Token::Whisper)
+        )
+    }
+
+    /// Consumes one token, or a whole brace-balanced block if the token opens
+    /// one, so recovery doesn't re-parse the body of a broken statement.
+    fn skip_token(&mut self) {
+        if !self.check(&Token::LBrace) {
+            self.advance();
+            return;
+        }
+        let mut depth = 0;
+        while let Some(token) = self.advance() {
+            match token {
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
         }
-        Ok(Ast::Program(statements))
     }
 
     fn declaration(&mut self) -> Result<Ast, LoveError> {
@@ -36,24 +129,26 @@ impl Parser {
     }
 
     fn var_declaration(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         let is_constant = matches!(self.peek(), Some(Token::Forever));
         self.advance(); // consume heart/forever
 
-        let name = match self.advance() {
+        let name = match self.peek() {
             Some(Token::Identifier(name)) => name.clone(),
-            _ => return Err(LoveError::Parser("Expected variable name".to_string())),
+            _ => return Err(self.error_at_current("variable name")),
         };
+        self.advance();
 
-
-
-        self.consume(&Token::Match, "Expected 'match' after variable name")?;
+        self.consume(&Token::Match, "after variable name")?;
         let initializer = self.expression()?;
-        self.consume(&Token::Semicolon, "Expected ';' after variable declaration")?;
+        self.consume(&Token::Semicolon, "after variable declaration")?;
 
         Ok(Ast::VariableDecl {
             name,
             is_constant,
             initializer: Box::new(initializer),
+            slot: None,
+            span: start.to(self.previous_span()),
         })
     }
 
@@ -63,23 +158,35 @@ impl Parser {
             Some(Token::Crush) => self.if_statement(),
             Some(Token::Dating) => self.while_statement(),
             Some(Token::Promise) => self.return_statement(),
-            Some(Token::LBrace) => Ok(Ast::Block(self.block()?)),
+            Some(Token::LBrace) => {
+                let start = self.peek_span();
+                let statements = self.block()?;
+                Ok(Ast::Block {
+                    statements,
+                    span: start.to(self.previous_span()),
+                })
+            }
             _ => self.expression_statement(),
         }
     }
 
     fn print_statement(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         self.advance(); // consume 'whisper'
         let value = self.expression()?;
-        self.consume(&Token::Semicolon, "Expected ';' after value")?;
-        Ok(Ast::PrintStmt(Box::new(value)))
+        self.consume(&Token::Semicolon, "after value")?;
+        Ok(Ast::PrintStmt {
+            value: Box::new(value),
+            span: start.to(self.previous_span()),
+        })
     }
 
     fn if_statement(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         self.advance(); // consume 'crush'
-        self.consume(&Token::LParen, "Expected '(' after 'crush'")?;
+        self.consume(&Token::LParen, "after 'crush'")?;
         let condition = self.expression()?;
-        self.consume(&Token::RParen, "Expected ')' after condition")?;
+        self.consume(&Token::RParen, "after condition")?;
 
         let then_branch = self.block()?;
         
@@ -94,27 +201,34 @@ impl Parser {
             condition: Box::new(condition),
             then_branch,
             else_branch,
+            span: start.to(self.previous_span()),
         })
     }
 
     fn while_statement(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         self.advance(); // consume 'dating'
-        self.consume(&Token::LParen, "Expected '(' after 'dating'")?;
+        self.consume(&Token::LParen, "after 'dating'")?;
         let condition = self.expression()?;
-        self.consume(&Token::RParen, "Expected ')' after condition")?;
+        self.consume(&Token::RParen, "after condition")?;
 
         let body = self.block()?;
 
         Ok(Ast::While {
             condition: Box::new(condition),
             body,
+            span: start.to(self.previous_span()),
         })
     }
 
     fn expression_statement(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         let expr = self.expression()?;
-        self.consume(&Token::Semicolon, "Expected ';' after expression")?;
-        Ok(Ast::ExpressionStmt(Box::new(expr)))
+        self.consume(&Token::Semicolon, "after expression")?;
+        Ok(Ast::ExpressionStmt {
+            expr: Box::new(expr),
+            span: start.to(self.previous_span()),
+        })
     }
 
     fn expression(&mut self) -> Result<Ast, LoveError> {
@@ -128,13 +242,16 @@ impl Parser {
             let value = self.assignment()?;
 
             match expr {
-                Ast::Variable(name) => {
+                Ast::Variable { name, span, .. } => {
+                    let span = span.to(value.span());
                     Ok(Ast::Assign {
                         name,
                         value: Box::new(value),
+                        slot: None,
+                        span,
                     })
                 }
-                _ => Err(LoveError::Parser("Invalid assignment target".to_string())),
+                _ => Err(LoveError::InvalidAssignmentTarget { span: expr.span() }),
             }
         } else {
             Ok(expr)
@@ -146,11 +263,7 @@ impl Parser {
         while matches!(self.peek(), Some(Token::Or)) {
             self.advance(); // consume 'or'
             let right = self.and()?;
-            expr = Ast::Binary {
-                left: Box::new(expr),
-                operator: BinaryOp::Or,
-                right: Box::new(right),
-            };
+            expr = binary(expr, BinaryOp::Or, right);
         }
 
         Ok(expr)
@@ -162,11 +275,7 @@ impl Parser {
         while matches!(self.peek(), Some(Token::And)) {
             self.advance(); // consume 'and'
             let right = self.equality()?;
-            expr = Ast::Binary {
-                left: Box::new(expr),
-                operator: BinaryOp::And,
-                right: Box::new(right),
-            };
+            expr = binary(expr, BinaryOp::And, right);
         }
 
         Ok(expr)
@@ -183,11 +292,7 @@ impl Parser {
             };
             self.advance();
             let right = self.comparison()?;
-            expr = Ast::Binary {
-                left: Box::new(expr),
-                operator: op,
-                right: Box::new(right),
-            };
+            expr = binary(expr, op, right);
         }
 
         Ok(expr)
@@ -206,11 +311,7 @@ impl Parser {
             };
             self.advance();
             let right = self.term()?;
-            expr = Ast::Binary {
-                left: Box::new(expr),
-                operator: op,
-                right: Box::new(right),
-            };
+            expr = binary(expr, op, right);
         }
 
         Ok(expr)
@@ -227,11 +328,7 @@ impl Parser {
             };
             self.advance();
             let right = self.factor()?;
-            expr = Ast::Binary {
-                left: Box::new(expr),
-                operator: op,
-                right: Box::new(right),
-            };
+            expr = binary(expr, op, right);
         }
 
         Ok(expr)
@@ -248,11 +345,7 @@ impl Parser {
             };
             self.advance();
             let right = self.unary()?;
-            expr = Ast::Binary {
-                left: Box::new(expr),
-                operator: op,
-                right: Box::new(right),
-            };
+            expr = binary(expr, op, right);
         }
 
         Ok(expr)
@@ -260,89 +353,166 @@ impl Parser {
 
     fn unary(&mut self) -> Result<Ast, LoveError> {
         if matches!(self.peek(), Some(Token::Not)) {
+            let start = self.peek_span();
             self.advance();
             let right = self.unary()?;
+            let span = start.to(right.span());
             return Ok(Ast::Unary {
                 operator: BinaryOp::Not,
                 operand: Box::new(right),
+                span,
             });
         }
         self.primary()
     }
 
     fn primary(&mut self) -> Result<Ast, LoveError> {
-        match self.advance() {
-            Some(Token::Number(n)) => Ok(Ast::Literal(Value::Number(*n as f64))),
-            Some(Token::Text(s)) => Ok(Ast::Literal(Value::Text(s.clone()))),
-            Some(Token::Yes) => Ok(Ast::Literal(Value::Boolean(true))),
-            Some(Token::No) => Ok(Ast::Literal(Value::Boolean(false))),
-            Some(Token::LParen) => {
+        let span = self.peek_span();
+        let literal = |value| Ok(Ast::Literal { value, span });
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error_at_current("expression")),
+        };
+        match token {
+            Token::Number(n) => {
+                self.advance();
+                literal(Value::Number(n as f64))
+            }
+            Token::Text(s) => {
+                self.advance();
+                literal(Value::Text(s))
+            }
+            Token::Yes => {
+                self.advance();
+                literal(Value::Boolean(true))
+            }
+            Token::No => {
+                self.advance();
+                literal(Value::Boolean(false))
+            }
+            Token::Lonely => {
+                self.advance();
+                literal(Value::Null)
+            }
+            Token::LParen => {
+                self.advance();
                 let expr = self.expression()?;
-                self.consume(&Token::RParen, "Expected ')' after expression")?;
-                Ok(Ast::Grouping(Box::new(expr)))
+                self.consume(&Token::RParen, "after expression")?;
+                Ok(Ast::Grouping {
+                    expr: Box::new(expr),
+                    span: span.to(self.previous_span()),
+                })
             }
-            Some(Token::Identifier(name)) => {
-                let name = name.clone();
+            Token::Identifier(name) => {
+                self.advance();
                 if self.check(&Token::LParen) {
                     self.advance(); // consume '('
-                    self.call_expression(name)
+                    self.call_expression(name, span)
                 } else {
-                    Ok(Ast::Variable(name))
+                    Ok(Ast::Variable { name, slot: None, span })
                 }
             }
-            _ => Err(LoveError::Parser("Expected expression".to_string())),
+            _ => Err(self.error_at_current("expression")),
         }
     }
     fn block(&mut self) -> Result<Vec<Ast>, LoveError> {
-        self.consume(&Token::LBrace, "Expected '{' before block")?;
+        self.consume(&Token::LBrace, "before block")?;
         let mut statements = Vec::new();
 
+        self.block_depth += 1;
         while !matches!(self.peek(), Some(Token::RBrace) | None) {
-            statements.push(self.declaration()?);
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize();
+                }
+            }
         }
+        self.block_depth -= 1;
 
-        self.consume(&Token::RBrace, "Expected '}' after block")?;
+        self.consume(&Token::RBrace, "after block")?;
         Ok(statements)
     }
 
     fn parse_type(&mut self) -> Result<Type, LoveError> {
-        match self.advance() {
-            Some(Token::TypeNumber) => Ok(Type::Number),
-            Some(Token::TypeText) => Ok(Type::Text),
-            Some(Token::TypeFeeling) => Ok(Type::Boolean),
-            _ => Err(LoveError::Parser("Expected type".to_string())),
-        }
+        let parsed = match self.peek() {
+            Some(Token::TypeNumber) => Type::Number,
+            Some(Token::TypeText) => Type::Text,
+            Some(Token::TypeFeeling) => Type::Boolean,
+            _ => return Err(self.error_at_current("type")),
+        };
+        self.advance();
+        Ok(parsed)
     }
 
     // Helper methods
     fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.current)
+        self.tokens.get(self.current).map(|t| &t.token)
     }
 
     fn advance(&mut self) -> Option<&Token> {
         if !self.is_at_end() {
             self.current += 1;
         }
-        self.tokens.get(self.current - 1)
+        self.current.checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|t| &t.token)
+    }
+
+    /// Span of the next token, or an empty span just past the last token
+    /// once the input is exhausted.
+    fn peek_span(&self) -> Span {
+        match self.tokens.get(self.current) {
+            Some(t) => t.span,
+            None => self.end_of_previous(),
+        }
+    }
+
+    fn previous(&self) -> Option<&Token> {
+        self.current.checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|t| &t.token)
+    }
+
+    fn previous_span(&self) -> Span {
+        self.current.checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|t| t.span)
+            .unwrap_or_default()
+    }
+
+    /// An empty span right after the previously consumed token, which is
+    /// where a missing terminator such as ';' belongs.
+    fn end_of_previous(&self) -> Span {
+        match self.current.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(t) => Span::new(t.span.end, t.span.end, t.span.line, t.span.column + t.span.len()),
+            None => Span::new(0, 0, 1, 1),
+        }
+    }
+
+    fn error_at_current(&self, expected: &str) -> LoveError {
+        LoveError::expected(expected, None, self.peek_span())
     }
 
     fn is_at_end(&self) -> bool {
         self.current >= self.tokens.len()
     }
 
-    fn consume(&mut self, expected: &Token, message: &str) -> Result<&Token, LoveError> {
-        if let Some(token) = self.peek() {
-            if token == expected {
-                Ok(self.advance().unwrap())
-            } else {
-                Err(LoveError::Parser(message.to_string()))
-            }
-        } else {
-            Err(LoveError::Parser(message.to_string()))
+    fn consume(&mut self, expected: &Token, context: &str) -> Result<&Token, LoveError> {
+        if self.check(expected) {
+            return Ok(self.advance().unwrap());
         }
+        let span = if *expected == Token::Semicolon {
+            self.end_of_previous()
+        } else {
+            self.peek_span()
+        };
+        Err(LoveError::expected(format!("'{}'", expected), Some(context), span))
     }
 
     fn return_statement(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         self.advance(); // consume 'promise'
         
         // Check if there's a return value
@@ -352,44 +522,51 @@ impl Parser {
             None
         };
         
-        self.consume(&Token::Semicolon, "Expected ';' after return value")?;
-        Ok(Ast::ReturnStmt(value))
+        self.consume(&Token::Semicolon, "after return value")?;
+        Ok(Ast::ReturnStmt {
+            value,
+            span: start.to(self.previous_span()),
+        })
     }
 
      fn function_declaration(&mut self) -> Result<Ast, LoveError> {
+        let start = self.peek_span();
         self.advance(); // consume 'devotion'
         
-        let name = match self.advance() {
+        let name = match self.peek() {
             Some(Token::Identifier(name)) => name.clone(),
-            _ => return Err(LoveError::Parser("Expected function name".to_string())),
+            _ => return Err(self.error_at_current("function name")),
         };
+        self.advance();
 
-        self.consume(&Token::LParen, "Expected '(' after function name")?;
+        self.consume(&Token::LParen, "after function name")?;
         
         // Parse parameters
         let mut params = Vec::new();
         if !matches!(self.peek(), Some(Token::RParen)) {
             loop {
-                let param_name = match self.advance() {
+                let param_name = match self.peek() {
                     Some(Token::Identifier(name)) => name.clone(),
-                    _ => return Err(LoveError::Parser("Expected parameter name".to_string())),
+                    _ => return Err(self.error_at_current("parameter name")),
                 };
+                let param_span = self.peek_span();
+                self.advance();
 
-                self.consume(&Token::Colon, "Expected ':' after parameter name")?;
+                self.consume(&Token::Colon, "after parameter name")?;
                 let param_type = self.parse_type()?;
-                params.push((param_name, param_type));
+                params.push((param_name, param_type, param_span));
 
                 match self.peek() {
                     Some(Token::Comma) => {
                         self.advance(); // consume comma
                     }
                     Some(Token::RParen) => break,
-                    _ => return Err(LoveError::Parser("Expected ',' or ')' after parameter".to_string())),
+                    _ => return Err(LoveError::expected("',' or ')'", Some("after parameter"), self.peek_span())),
                 }
             }
         }
         
-        self.consume(&Token::RParen, "Expected ')' after parameters")?;
+        self.consume(&Token::RParen, "after parameters")?;
 
         // Parse optional return type
         let return_type = if matches!(self.peek(), Some(Token::Arrow)) {
@@ -406,11 +583,13 @@ impl Parser {
             name,
             params,
             return_type,
-            body,
+            body: Rc::new(body),
+            slot: None,
+            span: start.to(self.previous_span()),
         })
     }
 
-    fn call_expression(&mut self, name: String) -> Result<Ast, LoveError> {
+    fn call_expression(&mut self, name: String, start: Span) -> Result<Ast, LoveError> {
         let mut arguments = Vec::new();
         
         // Parse arguments
@@ -425,11 +604,13 @@ impl Parser {
             }
         }
         
-        self.consume(&Token::RParen, "Expected ')' after arguments")?;
+        self.consume(&Token::RParen, "after arguments")?;
         
         Ok(Ast::Call {
             callee: name,
             arguments,
+            slot: None,
+            span: start.to(self.previous_span()),
         })
     }
 
@@ -440,4 +621,14 @@ impl Parser {
         }
     }
 
-}
\ No newline at end of file
+}
+
+fn binary(left: Ast, operator: BinaryOp, right: Ast) -> Ast {
+    let span = left.span().to(right.span());
+    Ast::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+        span,
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::error::LoveError;
+use crate::parser::ast::{Ast, Slot};
+use crate::span::Span;
+
+/// The names declared in one scope, and references made inside it that
+/// didn't find a declaration yet.
+#[derive(Default)]
+struct Scope {
+    names: HashMap<String, (usize, Span)>,
+    pending: Vec<(String, Span)>,
+}
+
+impl Scope {
+    /// Records a declaration of `name`, reporting duplicates and earlier
+    /// uses. Returns the slot index the name lives in.
+    fn declare(&mut self, name: &str, span: Span, errors: &mut Vec<LoveError>) -> usize {
+        if let Some(&(index, previous)) = self.names.get(name) {
+            errors.push(LoveError::DuplicateDeclaration { name: name.to_string(), span, previous });
+            return index;
+        }
+        if let Some(&(_, used)) = self.pending.iter().find(|(pending, _)| pending == name) {
+            errors.push(LoveError::UseBeforeDeclaration { name: name.to_string(), span: used });
+            self.pending.retain(|(pending, _)| pending != name);
+        }
+        let index = self.names.len();
+        self.names.insert(name.to_string(), (index, span));
+        index
+    }
+}
+
+/// Binds every name in a love story to where it lives before it runs.
+///
+/// Names declared inside blocks and devotions get a `Slot` (how many scopes
+/// out, and which index in that scope), so the interpreter can find them
+/// without hashing. Everything else is a global and stays late-bound, which
+/// is what lets devotions call each other in any order. Along the way the
+/// resolver reports names used before their declaration, names declared
+/// twice in the same scope, and devotions declared anywhere but the top
+/// level.
+pub struct Resolver {
+    /// Globals declared by stories resolved earlier, e.g. previous REPL
+    /// lines.
+    known_globals: HashSet<String>,
+    globals: Scope,
+    /// Local scopes, innermost last, for the top-level code and then for
+    /// each devotion being resolved. A devotion can't see its caller's
+    /// locals, so lookups never cross into an outer entry.
+    functions: Vec<Vec<Scope>>,
+    errors: Vec<LoveError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            known_globals: HashSet::new(),
+            globals: Scope::default(),
+            functions: vec![Vec::new()],
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolves `ast` in place, returning every problem found: a single
+    /// error as-is, several as `LoveError::Multiple`.
+    pub fn resolve(&mut self, ast: &mut Ast) -> Result<(), LoveError> {
+        self.globals = Scope::default();
+        self.functions = vec![Vec::new()];
+        self.node(ast);
+
+        let declared = std::mem::take(&mut self.globals.names);
+        self.known_globals.extend(declared.into_keys());
+
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|e| e.span().map(|s| s.start));
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(LoveError::Multiple { errors }),
+        }
+    }
+
+    fn scopes(&mut self) -> &mut Vec<Scope> {
+        self.functions.last_mut().expect("there is always a function being resolved")
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes().push(Scope::default());
+    }
+
+    /// Closes the innermost scope. References it couldn't resolve move out
+    /// to the enclosing scope of the same devotion, where a later
+    /// declaration still counts as use-before-declaration.
+    fn end_scope(&mut self) {
+        let scope = self.scopes().pop().expect("scopes are balanced");
+        match self.scopes().last_mut() {
+            Some(parent) => parent.pending.extend(scope.pending),
+            None => self.unresolved_globals(scope.pending),
+        }
+    }
+
+    /// Keeps top-level references to globals that haven't been declared
+    /// yet, so declaring them later in the story can be reported.
+    fn unresolved_globals(&mut self, references: Vec<(String, Span)>) {
+        for (name, span) in references {
+            if !self.globals.names.contains_key(&name) && !self.known_globals.contains(&name) {
+                self.globals.pending.push((name, span));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, span: Span) -> Option<Slot> {
+        let mut errors = std::mem::take(&mut self.errors);
+        let slot = match self.scopes().last_mut() {
+            Some(scope) => Some(Slot { depth: 0, index: scope.declare(name, span, &mut errors) }),
+            None => {
+                self.globals.declare(name, span, &mut errors);
+                None
+            }
+        };
+        self.errors = errors;
+        slot
+    }
+
+    /// Finds the local `name` refers to, or remembers the reference as a
+    /// global one.
+    fn lookup(&mut self, name: &str, span: Span) -> Option<Slot> {
+        let scopes = self.scopes();
+        let found = scopes.iter().rev().enumerate()
+            .find_map(|(depth, scope)| scope.names.get(name).map(|&(index, _)| Slot { depth, index }));
+        if found.is_none() {
+            match self.scopes().last_mut() {
+                Some(scope) => scope.pending.push((name.to_string(), span)),
+                None => self.unresolved_globals(vec![(name.to_string(), span)]),
+            }
+        }
+        found
+    }
+
+    fn nodes(&mut self, nodes: &mut [Ast]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, ast: &mut Ast) {
+        match ast {
+            Ast::Program { statements, .. } => self.nodes(statements),
+            Ast::Block { statements, .. } => {
+                self.begin_scope();
+                self.nodes(statements);
+                self.end_scope();
+            }
+            Ast::VariableDecl { name, initializer, slot, span, .. } => {
+                self.node(initializer);
+                *slot = self.declare(name, *span);
+            }
+            Ast::FunctionDecl { name, params, body, slot, span, .. } => {
+                // A devotion's body only sees globals, so one declared in a
+                // block couldn't even call itself
+                if self.functions.len() > 1 || !self.scopes().is_empty() {
+                    self.errors.push(LoveError::NestedDevotion { name: name.clone(), span: *span });
+                }
+                *slot = self.declare(name, *span);
+                self.functions.push(vec![Scope::default()]);
+                for (param, _, param_span) in params.iter() {
+                    self.declare(param, *param_span);
+                }
+                self.nodes(Rc::make_mut(body).as_mut_slice());
+                self.functions.pop();
+            }
+            Ast::Call { callee, arguments, slot, span } => {
+                *slot = self.lookup(callee, *span);
+                self.nodes(arguments);
+            }
+            Ast::If { condition, then_branch, else_branch, .. } => {
+                self.node(condition);
+                self.nodes(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.nodes(else_branch);
+                }
+            }
+            Ast::While { condition, body, .. } => {
+                self.node(condition);
+                self.nodes(body);
+            }
+            Ast::ExpressionStmt { expr, .. }
+            | Ast::Grouping { expr, .. }
+            | Ast::PrintStmt { value: expr, .. } => self.node(expr),
+            Ast::ReturnStmt { value, .. } => {
+                if let Some(value) = value {
+                    self.node(value);
+                }
+            }
+            Ast::Binary { left, right, .. } => {
+                self.node(left);
+                self.node(right);
+            }
+            Ast::Unary { operand, .. } => self.node(operand),
+            Ast::Assign { name, value, slot, span } => {
+                self.node(value);
+                *slot = self.lookup(name, *span);
+            }
+            Ast::Variable { name, slot, span } => *slot = self.lookup(name, *span),
+            Ast::Literal { .. } => {}
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
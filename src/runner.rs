@@ -1,97 +1,334 @@
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use colored::*;
 
+use crate::checker::check;
+use crate::diagnostic::render_error;
 use crate::error::LoveError;
+use crate::formatter;
+use crate::highlight::{highlight, to_json};
 use crate::interpreter::Interpreter;
 use crate::lexer::Lexer;
+use crate::linter::{self, LintConfig, Severity};
+use crate::optimizer::optimize;
+use crate::parser::ast::Ast;
 use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::vm::Vm;
 use crate::fun::*;
 
+/// Which engine runs love stories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Walks the syntax tree directly.
+    #[default]
+    TreeWalker,
+    /// Compiles to bytecode and runs it on a stack VM.
+    Vm,
+}
+
+/// Where a love story is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A `.love` file on disk.
+    File(PathBuf),
+    /// Everything written to standard input.
+    Stdin,
+    /// Code given directly, e.g. with `love -e`.
+    Inline(String),
+}
+
+impl Source {
+    /// How to refer to the story in reports.
+    pub fn origin(&self) -> String {
+        match self {
+            Source::File(path) => path.display().to_string(),
+            Source::Stdin => "<stdin>".to_string(),
+            Source::Inline(_) => "<inline>".to_string(),
+        }
+    }
+
+    /// Reads the story's code. Only files need the `.love` extension.
+    fn read(&self) -> Result<String, LoveError> {
+        match self {
+            Source::File(path) => {
+                if path.extension().is_none_or(|extension| extension != "love") {
+                    return Err(LoveError::NotALoveFile { path: self.origin() });
+                }
+                fs::read_to_string(path).map_err(|e| LoveError::io("read love letter", e))
+            }
+            Source::Stdin => {
+                let mut content = String::new();
+                io::stdin().read_to_string(&mut content)
+                    .map_err(|e| LoveError::io("read love letter from stdin", e))?;
+                Ok(content)
+            }
+            Source::Inline(code) => Ok(code.clone()),
+        }
+    }
+}
+
+enum Backend {
+    TreeWalker(Interpreter),
+    Vm(Vm),
+}
+
 pub struct Runner {
-    interpreter: Interpreter,
+    backend: Backend,
+    optimize: bool,
+    plain: bool,
 }
 
 impl Runner {
     pub fn new() -> Self {
-        Runner {
-            interpreter: Interpreter::new(),
+        Runner::with_output(Box::new(io::stdout()))
+    }
+
+    /// Creates a runner whose script output (`whisper`) goes to `output`.
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        Runner::with_engine(Engine::default(), output)
+    }
+
+    /// Creates a runner that uses `engine` and sends script output to
+    /// `output`.
+    pub fn with_engine(engine: Engine, output: Box<dyn Write>) -> Self {
+        let backend = match engine {
+            Engine::TreeWalker => Backend::TreeWalker(Interpreter::with_output(output)),
+            Engine::Vm => Backend::Vm(Vm::with_output(output)),
+        };
+        Runner { backend, optimize: false, plain: false }
+    }
+
+    /// Whether love stories go through the optimizer before they run.
+    pub fn set_optimize(&mut self, enabled: bool) {
+        self.optimize = enabled;
+    }
+
+    /// Whether to leave out the banners around a story's output and the
+    /// flavor text in reports, so that only `whisper` output is printed to
+    /// stdout and only diagnostics to stderr.
+    pub fn set_plain(&mut self, enabled: bool) {
+        self.plain = enabled;
+    }
+
+    /// Sets the arguments love stories read with `letters()` and
+    /// `letter(n)`.
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        match &mut self.backend {
+            Backend::TreeWalker(interpreter) => interpreter.set_args(args),
+            Backend::Vm(vm) => vm.set_args(args),
         }
     }
 
-    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LoveError> {
-        let path = path.as_ref();
-        
-        // Check file extension
-        if let Some(extension) = path.extension() {
-            if extension != "love" {
-                return Err(LoveError::Runtime(
-                    "Only .love files can contain our love story! 💝".to_string()
-                ));
+    /// Whether love stories may read environment variables with
+    /// `secret(name)`.
+    pub fn set_allow_env(&mut self, allowed: bool) {
+        match &mut self.backend {
+            Backend::TreeWalker(interpreter) => interpreter.set_allow_env(allowed),
+            Backend::Vm(vm) => vm.set_allow_env(allowed),
+        }
+    }
+
+    /// Parses, resolves and type-checks a love story without running it,
+    /// reporting every problem found.
+    pub fn check(&mut self, source: &Source) -> Result<(), LoveError> {
+        let (origin, content) = self.load(source)?;
+        let mut ast = self.parse(&content, &origin)?;
+        Resolver::new().resolve(&mut ast)
+            .and_then(|_| check(&ast))
+            .map_err(|e| self.heartbreak(e, &content, &origin))?;
+        if !self.plain {
+            println!("{}", create_love_border(
+                &format!("{} {} is free of heartbreak!", get_random_emoji(), origin)
+            ).bright_green());
+        }
+        Ok(())
+    }
+
+    /// Prints the tokens of a love story, one per line with the line and
+    /// column each starts at.
+    pub fn print_tokens(&mut self, source: &Source) -> Result<(), LoveError> {
+        let (origin, content) = self.load(source)?;
+        let tokens = Lexer::new(&content).tokenize()
+            .map_err(|e| self.heartbreak(e, &content, &origin))?;
+        let listing: String = tokens.iter()
+            .map(|t| format!("{}:{}\t{:?}\n", t.span.line, t.span.column, t.token))
+            .collect();
+        emit(&listing);
+        Ok(())
+    }
+
+    /// Prints the class of every token and comment of a love story as
+    /// JSON, for syntax highlighting.
+    pub fn print_highlights(&mut self, source: &Source) -> Result<(), LoveError> {
+        let (origin, content) = self.load(source)?;
+        let highlights = highlight(&content)
+            .map_err(|e| self.heartbreak(e, &content, &origin))?;
+        emit(&format!("{}\n", to_json(&highlights, &content)));
+        Ok(())
+    }
+
+    /// Prints a love story in the canonical style.
+    pub fn format(&mut self, source: &Source) -> Result<(), LoveError> {
+        let (origin, content) = self.load(source)?;
+        emit(&self.formatted(&content, &origin)?);
+        Ok(())
+    }
+
+    /// Reports whether a love story is already in the canonical style,
+    /// saying so when it isn't.
+    pub fn check_format(&mut self, source: &Source) -> Result<bool, LoveError> {
+        let (origin, content) = self.load(source)?;
+        let formatted = content == self.formatted(&content, &origin)?;
+        match (formatted, self.plain) {
+            (true, true) => {}
+            (true, false) => println!("{}", create_love_border(
+                &format!("{} {} is already beautifully formatted!", get_random_emoji(), origin)
+            ).bright_green()),
+            (false, true) => println!("{}: not formatted", origin),
+            (false, false) => println!("{}", create_love_border(
+                &format!("💔 {} needs some grooming: run love fmt on it", origin)
+            ).bright_red()),
+        }
+        Ok(formatted)
+    }
+
+    /// `content` in the canonical style, comments and all.
+    fn formatted(&self, content: &str, origin: &str) -> Result<String, LoveError> {
+        let mut lexer = Lexer::new(content);
+        let ast = lexer.tokenize()
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .map_err(|e| self.heartbreak(e, content, origin))?;
+        Ok(formatter::format(&ast, lexer.trivia()))
+    }
+
+    /// Looks for likely mistakes in a love story without running it,
+    /// printing each lint `config` doesn't allow to stderr, like any other
+    /// diagnostic. Returns whether none of them was denied.
+    pub fn lint(&mut self, source: &Source, config: &LintConfig) -> Result<bool, LoveError> {
+        let (origin, content) = self.load(source)?;
+        let ast = self.parse(&content, &origin)?;
+        let lints = linter::lint(&ast, config);
+        let report: String = lints.iter()
+            .map(|lint| format!("{}\n", lint.diagnostic().render(&content, &origin)))
+            .collect();
+        eprint!("{}", report);
+
+        let denied = lints.iter().filter(|lint| lint.severity == Severity::Deny).count();
+        if !self.plain {
+            let plural = if lints.len() == 1 { "" } else { "s" };
+            let summary = format!("{} {} lint{} found in {}", get_random_emoji(), lints.len(), plural, origin);
+            match (lints.len(), denied) {
+                (0, _) => println!("{}", create_love_border(
+                    &format!("{} {} is a flawless love story!", get_random_emoji(), origin)
+                ).bright_green()),
+                (_, 0) => println!("{}", summary.bright_yellow().bold()),
+                _ => println!("{}", summary.bright_red().bold()),
             }
-        } else {
-            return Err(LoveError::Runtime(
-                "File must have a .love extension! 💝".to_string()
-            ));
         }
+        Ok(denied == 0)
+    }
+
+    /// Prints the syntax tree of a love story, as it would run (so
+    /// optimized when the optimizer is on), instead of running it.
+    pub fn print_ast(&mut self, source: &Source) -> Result<(), LoveError> {
+        let (origin, content) = self.load(source)?;
+        let ast = self.parse(&content, &origin)?;
+        emit(&ast.to_string());
+        Ok(())
+    }
+
+    /// Lexes and parses `content`, optimizing the result if asked to.
+    fn parse(&self, content: &str, origin: &str) -> Result<Ast, LoveError> {
+        let parsed = Lexer::new(content).tokenize()
+            .and_then(|tokens| Parser::new(tokens).parse());
+        match parsed {
+            Ok(ast) if self.optimize => Ok(optimize(ast)),
+            Ok(ast) => Ok(ast),
+            Err(e) => Err(self.heartbreak(e, content, origin)),
+        }
+    }
+
+    /// Runs a `.love` file, reporting any error as a diagnostic before
+    /// returning it.
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LoveError> {
+        self.run(&Source::File(path.as_ref().to_path_buf()))
+    }
 
-        // Read file content
-        let content = fs::read_to_string(path)
-            .map_err(|e| LoveError::Runtime(format!("Failed to read love letter: {}", e)))?;
+    /// Runs a love story, reporting any error as a diagnostic before
+    /// returning it. A story that ends itself with `goodbye` returns
+    /// `LoveError::Goodbye` without a report.
+    pub fn run(&mut self, source: &Source) -> Result<(), LoveError> {
+        let (origin, content) = self.load(source)?;
 
-        println!("{}", create_love_border(
-            &format!("💌 Reading love story from: {}", path.display())
-        ).bright_cyan());
+        if !self.plain {
+            println!("{}", create_love_border(
+                &format!("💌 Reading love story from: {}", origin)
+            ).bright_cyan());
 
-        // Print the content being executed
-        println!("{}", "💝 Love story output:".bright_yellow());
-        println!();
+            // Print the content being executed
+            println!("{}", "💝 Love story output:".bright_yellow());
+            println!();
+        }
 
         // Execute the code
-        let mut lexer = Lexer::new(&content);
-        let tokens = match lexer.tokenize() {
-            Ok(t) => t,
-            Err(e) => {
-                println!("{}", create_love_border(
-                    &format!("💔 Lexer error:\n{}", format_error(&e))
-                ).bright_red());
-                return Err(e);
-            }
-        };
+        let ast = self.parse(&content, &origin)?;
 
-        let mut parser = Parser::new(tokens);
-        let ast = match parser.parse() {
-            Ok(a) => a,
-            Err(e) => {
-                println!("{}", create_love_border(
-                    &format!("💔 Parser error:\n{}", format_error(&e))
-                ).bright_red());
-                return Err(e);
-            }
+        let result = match &mut self.backend {
+            Backend::TreeWalker(interpreter) => interpreter.interpret(ast),
+            Backend::Vm(vm) => vm.interpret(ast),
         };
-
-        match self.interpreter.interpret(ast) {
+        match result {
             Ok(_) => {
-                println!("{}", create_love_border(
-                    &format!("{} Love story executed successfully!", get_random_emoji())
-                ).bright_green());
+                if !self.plain {
+                    println!("{}", create_love_border(
+                        &format!("{} Love story executed successfully!", get_random_emoji())
+                    ).bright_green());
+                }
                 Ok(())
             },
-            Err(e) => {
-                println!("{}", create_love_border(
-                    &format!("💔 Runtime error:\n{}", format_error(&e))
-                ).bright_red());
-                Err(e)
-            }
+            Err(e @ LoveError::Goodbye { .. }) => Err(e),
+            Err(e) => Err(self.heartbreak(e, &content, &origin)),
+        }
+    }
+
+    /// Reads a love story, returning how to refer to it in reports along
+    /// with its content.
+    fn load(&self, source: &Source) -> Result<(String, String), LoveError> {
+        let origin = source.origin();
+        let content = source.read().map_err(|e| self.report(e, "", &origin))?;
+        Ok((origin, content))
+    }
+
+    /// Prints a header naming the kind of `error`, then reports it.
+    fn heartbreak(&self, error: LoveError, source: &str, origin: &str) -> LoveError {
+        if !self.plain {
+            eprintln!("{}", format!("💔 {}:", error.kind()).bright_red());
         }
+        self.report(error, source, origin)
+    }
+
+    /// Prints `error` as a diagnostic against `source` to stderr, keeping
+    /// stdout for what the story whispers, and hands it back.
+    fn report(&self, error: LoveError, source: &str, origin: &str) -> LoveError {
+        if self.plain {
+            eprint!("{}", render_error(&error, source, origin, None));
+        } else {
+            eprintln!("{}", render_error(&error, source, origin, get_random_error_message()));
+        }
+        error
     }
 }
 
-fn format_error(error: &LoveError) -> String {
-    match error {
-        LoveError::Lexer(msg) => format!("{}\n{}", get_random_error_message(), msg),
-        LoveError::Parser(msg) => format!("{}\n{}", get_random_error_message(), msg),
-        LoveError::Runtime(msg) => format!("{}\n{}", get_random_error_message(), msg),
-        LoveError::Type(msg) => format!("{}\n{}", get_random_error_message(), msg),
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// Writes a listing to stdout. Whoever reads it may stop early (`| head`),
+/// which isn't worth reporting.
+fn emit(text: &str) {
+    let _ = io::stdout().lock().write_all(text.as_bytes());
+}
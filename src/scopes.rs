@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+/// What the passes that walk a love story without running it (the
+/// checker, optimizer, linter and language server) know about each name,
+/// scoped the way the interpreter scopes names.
+///
+/// Blocks open scopes; `crush` and `dating` bodies don't. A devotion gets
+/// a fresh stack of scopes and sees only its own names and the globals,
+/// never the locals of the code around it.
+pub(crate) struct Scopes<T> {
+    globals: HashMap<String, T>,
+    /// Local scopes, innermost last, for the top-level code and then for
+    /// each devotion being walked.
+    devotions: Vec<Vec<HashMap<String, T>>>,
+}
+
+impl<T> Scopes<T> {
+    pub fn new() -> Self {
+        Scopes { globals: HashMap::new(), devotions: vec![Vec::new()] }
+    }
+
+    fn locals(&self) -> &Vec<HashMap<String, T>> {
+        self.devotions.last().expect("there is always a scope stack")
+    }
+
+    fn locals_mut(&mut self) -> &mut Vec<HashMap<String, T>> {
+        self.devotions.last_mut().expect("there is always a scope stack")
+    }
+
+    pub fn begin_block(&mut self) {
+        self.locals_mut().push(HashMap::new());
+    }
+
+    /// Closes the innermost block, returning what was declared in it.
+    pub fn end_block(&mut self) -> HashMap<String, T> {
+        self.locals_mut().pop().expect("scopes are balanced")
+    }
+
+    /// Starts walking a devotion's body, with its parameters as its
+    /// outermost scope.
+    pub fn begin_devotion(&mut self, params: HashMap<String, T>) {
+        self.devotions.push(vec![params]);
+    }
+
+    /// Finishes a devotion's body, returning its scopes, parameters first.
+    pub fn end_devotion(&mut self) -> Vec<HashMap<String, T>> {
+        self.devotions.pop().expect("scope stacks are balanced")
+    }
+
+    /// Declares `name` in the innermost scope, or as a global outside any
+    /// block or devotion.
+    pub fn declare(&mut self, name: &str, value: T) {
+        match self.locals_mut().last_mut() {
+            Some(scope) => scope.insert(name.to_string(), value),
+            None => self.globals.insert(name.to_string(), value),
+        };
+    }
+
+    /// The innermost local called `name` that can be seen from here.
+    pub fn local(&self, name: &str) -> Option<&T> {
+        self.locals().iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    pub fn local_mut(&mut self, name: &str) -> Option<&mut T> {
+        self.locals_mut().iter_mut().rev().find_map(|scope| scope.get_mut(name))
+    }
+
+    /// What `name` refers to here: a local, or failing that a global
+    /// declared so far.
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.local(name).or_else(|| self.globals.get(name))
+    }
+
+    /// Every global declared, once the walk is done.
+    pub fn into_globals(self) -> HashMap<String, T> {
+        self.globals
+    }
+}
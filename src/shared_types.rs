@@ -1,5 +1,8 @@
 use std::fmt;
+use std::rc::Rc;
 use crate::parser::ast::Ast;
+use crate::builtins::Builtin;
+use crate::vm::Function;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -8,9 +11,13 @@ pub enum Value {
     Boolean(bool),
      Function {
         name: String,
-        params: Vec<String>,
-        body: Vec<Ast>,
+        params: Rc<[String]>,
+        body: Rc<Vec<Ast>>,
     },
+    /// A devotion compiled to bytecode, as created by the VM.
+    Compiled(Rc<Function>),
+    /// A devotion built into the language.
+    Builtin(Builtin),
     Null,
 }
 
@@ -31,12 +38,34 @@ pub enum BinaryOp {
     Not,           // not
 }
 
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let word = match self {
+            BinaryOp::Add => "cuddle",
+            BinaryOp::Subtract => "breakup",
+            BinaryOp::Multiply => "kiss",
+            BinaryOp::Divide => "split",
+            BinaryOp::Equal => "soulmate",
+            BinaryOp::NotEqual => "heartbreak",
+            BinaryOp::Less => "envies",
+            BinaryOp::Greater => "admires",
+            BinaryOp::LessEqual => "yearns",
+            BinaryOp::GreaterEqual => "adores",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Not => "not",
+        };
+        write!(f, "{}", word)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Number,
     Text,
     Boolean,
     Function,
+    Null,
 }
 
 impl fmt::Display for Type {
@@ -46,18 +75,31 @@ impl fmt::Display for Type {
             Type::Text => write!(f, "text"),
             Type::Boolean => write!(f, "feeling"),
             Type::Function => write!(f, "devotion"),
+            Type::Null => write!(f, "lonely"),
         }
     }
 }
 
-// Add Display implementation for Value too for better error messages
+/// Renders a value the way `whisper` shows it to a human.
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Value::Number(_) => write!(f, "number"),
-            Value::Text(_) => write!(f, "text"),
-            Value::Boolean(_) => write!(f, "feeling"),
-            Value::Function { .. } => write!(f, "devotion"),
+            // -0.0 would otherwise print as "-0"
+            Value::Number(n) if *n == 0.0 => write!(f, "0"),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Boolean(true) => write!(f, "yes"),
+            Value::Boolean(false) => write!(f, "no"),
+            Value::Function { name, params, .. } => {
+                write!(f, "<devotion {}({})>", name, params.join(", "))
+            }
+            Value::Compiled(function) => {
+                write!(f, "<devotion {}({})>", function.name, function.params.join(", "))
+            }
+            Value::Builtin(builtin) => {
+                let params: Vec<&str> = builtin.params().iter().map(|(name, _)| *name).collect();
+                write!(f, "<devotion {}({})>", builtin.name(), params.join(", "))
+            }
             Value::Null => write!(f, "lonely"),
         }
     }
@@ -70,8 +112,37 @@ impl Value {
             Value::Number(_) => Type::Number,
             Value::Text(_) => Type::Text,
             Value::Boolean(_) => Type::Boolean,
-            Value::Function { .. } => Type::Function,
-            Value::Null => Type::Number, // You might want to handle null differently
+            Value::Function { .. } | Value::Compiled(_) | Value::Builtin(_) => Type::Function,
+            Value::Null => Type::Null,
+        }
+    }
+
+    /// A rough count of the bytes this value occupies, used to enforce
+    /// memory limits. Devotion bodies are not counted.
+    pub fn approx_size(&self) -> usize {
+        let own = std::mem::size_of::<Value>();
+        match self {
+            Value::Text(s) => own + s.len(),
+            // Both engines' devotions count the same, whatever they carry
+            Value::Function { name, params, .. } => own + name.len() + params_size(params),
+            Value::Compiled(function) => own + function.name.len() + params_size(&function.params),
+            Value::Number(_) | Value::Boolean(_) | Value::Builtin(_) | Value::Null => own,
+        }
+    }
+
+    /// The love-themed name of this value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Text(_) => "text",
+            Value::Boolean(_) => "feeling",
+            Value::Function { .. } | Value::Compiled(_) | Value::Builtin(_) => "devotion",
+            Value::Null => "lonely",
         }
     }
-}
\ No newline at end of file
+}
+
+/// What a devotion's parameter names add to its size.
+fn params_size(params: &[String]) -> usize {
+    params.iter().map(|p| p.len() + std::mem::size_of::<String>()).sum()
+}
@@ -0,0 +1,101 @@
+use crate::shared_types::{BinaryOp, Value};
+use crate::span::Span;
+
+/// One VM instruction. Operands index into the chunk's constant pool, the
+/// current frame's local slots, or the instruction list (for jumps).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// Push a constant.
+    Constant(u32),
+    /// Push `lonely`.
+    Null,
+    /// Discard the top of the stack.
+    Pop,
+    /// Bind the top of the stack to a global name, leaving it in place.
+    DefineGlobal(u32),
+    /// Push the value of a global name.
+    GetGlobal(u32),
+    /// Store the top of the stack into an existing global, leaving it in place.
+    SetGlobal(u32),
+    /// Bind the top of the stack to a local slot, leaving it in place.
+    DefineLocal(u16),
+    /// Push the value of a local slot. `name` is only used to report a slot
+    /// whose declaration hasn't run yet.
+    GetLocal { slot: u16, name: u32 },
+    /// Store the top of the stack into a declared local, leaving it in place.
+    SetLocal { slot: u16, name: u32 },
+    /// Empty `count` local slots from `from` on, releasing what they held.
+    /// Emitted where a block ends.
+    ClearLocals { from: u16, count: u16 },
+    /// Push the devotion about to be called, from a local slot if it has
+    /// one and from the globals otherwise.
+    GetCallee { name: u32, slot: Option<u16> },
+    /// Pop two operands and push the result of the operator.
+    Binary(BinaryOp),
+    /// Pop one operand and push the result of the operator.
+    Unary(BinaryOp),
+    /// Fail unless the top of the stack is a feeling.
+    CheckFeeling,
+    /// Pop a value and whisper it, then push `lonely`.
+    Print,
+    Jump(u32),
+    /// Pop a condition and jump if it is `no`.
+    JumpIfFalse(u32),
+    /// Jump, keeping the top of the stack, if it is `no`; otherwise pop it.
+    /// Used to short-circuit 'and'.
+    JumpIfFalseOrPop(u32),
+    /// Jump, keeping the top of the stack, if it is `yes`; otherwise pop it.
+    /// Used to short-circuit 'or'.
+    JumpIfTrueOrPop(u32),
+    /// Check that the value on top of the stack is a devotion taking `argc`
+    /// arguments, before any argument is evaluated. `name` is the callee as
+    /// written at the call site.
+    PrepareCall { name: u32, argc: u8 },
+    /// Call the devotion sitting below its `argc` arguments.
+    Call { name: u32, argc: u8 },
+    /// Return the top of the stack from the current devotion.
+    Return,
+}
+
+/// A compiled sequence of instructions with the source span each one came
+/// from, for error reporting.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.code.push(instruction);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> u32 {
+        if let Some(index) = self.constants.iter().position(|c| *c == value) {
+            return index as u32;
+        }
+        self.constants.push(value);
+        (self.constants.len() - 1) as u32
+    }
+
+    /// The name stored in the constant pool at `index`.
+    pub fn name(&self, index: u32) -> &str {
+        match &self.constants[index as usize] {
+            Value::Text(name) => name,
+            _ => "",
+        }
+    }
+}
+
+/// A devotion (or the top-level story) compiled to bytecode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub chunk: Chunk,
+    /// How many local slots a call needs, parameters included.
+    pub slot_count: usize,
+}
@@ -0,0 +1,298 @@
+use std::rc::Rc;
+
+use crate::error::LoveError;
+use crate::parser::ast::{Ast, Slot};
+use crate::shared_types::{BinaryOp, Value};
+use crate::span::Span;
+use super::chunk::{Chunk, Function, Instruction};
+
+/// Compiles a parsed love story into the top-level function the VM runs.
+pub fn compile(program: &Ast) -> Result<Function, LoveError> {
+    let mut compiler = Compiler {
+        state: FunctionState::new("<story>", Vec::new()),
+    };
+    match program {
+        Ast::Program { statements, span } => compiler.sequence(statements, *span)?,
+        other => compiler.statement(other)?,
+    }
+    compiler.emit(Instruction::Return, program.span());
+    Ok(compiler.state.finish())
+}
+
+/// A local scope open in the function being compiled. Its names take the
+/// frame slots from `base` on, in the order the resolver numbered them.
+struct Scope {
+    base: usize,
+    width: usize,
+}
+
+/// Book-keeping for the function currently being compiled.
+struct FunctionState {
+    name: String,
+    params: Vec<String>,
+    chunk: Chunk,
+    /// Open scopes, innermost last. Empty at the top level of the story,
+    /// where every name is a global.
+    scopes: Vec<Scope>,
+    slot_count: usize,
+}
+
+impl FunctionState {
+    fn new(name: &str, params: Vec<String>) -> Self {
+        FunctionState {
+            name: name.to_string(),
+            params,
+            chunk: Chunk::default(),
+            scopes: Vec::new(),
+            slot_count: 0,
+        }
+    }
+
+    fn finish(self) -> Function {
+        Function {
+            name: self.name,
+            params: self.params,
+            chunk: self.chunk,
+            slot_count: self.slot_count,
+        }
+    }
+}
+
+struct Compiler {
+    state: FunctionState,
+}
+
+/// Where a name lives at runtime.
+enum Binding {
+    Local(u16),
+    Global(u32),
+}
+
+impl Compiler {
+    fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.state.chunk.emit(instruction, span)
+    }
+
+    fn constant(&mut self, value: Value) -> u32 {
+        self.state.chunk.add_constant(value)
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        self.constant(Value::Text(name.to_string()))
+    }
+
+    fn here(&self) -> u32 {
+        self.state.chunk.code.len() as u32
+    }
+
+    /// Points the jump at `at` to the next instruction to be emitted.
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+        match &mut self.state.chunk.code[at] {
+            Instruction::Jump(t)
+            | Instruction::JumpIfFalse(t)
+            | Instruction::JumpIfFalseOrPop(t)
+            | Instruction::JumpIfTrueOrPop(t) => *t = target,
+            _ => {}
+        }
+    }
+
+    /// Turns the resolver's answer for `name` into where it lives at
+    /// runtime: a frame slot for locals, late-bound by name otherwise.
+    fn binding(&mut self, name: &str, slot: Option<Slot>, span: Span) -> Result<Binding, LoveError> {
+        let Some(Slot { depth, index }) = slot else {
+            return Ok(Binding::Global(self.name(name)));
+        };
+        let scopes = &mut self.state.scopes;
+        let at = scopes.len().checked_sub(depth + 1).expect("the resolver only hands out slots of open scopes");
+        let scope = &mut scopes[at];
+        scope.width = scope.width.max(index + 1);
+        let flat = scope.base + index;
+        self.state.slot_count = self.state.slot_count.max(flat + 1);
+        let slot = u16::try_from(flat).map_err(|_| LoveError::Runtime {
+            message: "Too many variables in one devotion".to_string(),
+            span: Some(span),
+        })?;
+        Ok(Binding::Local(slot))
+    }
+
+    fn define(&mut self, name: &str, slot: Option<Slot>, span: Span) -> Result<(), LoveError> {
+        match self.binding(name, slot, span)? {
+            Binding::Local(slot) => self.emit(Instruction::DefineLocal(slot), span),
+            Binding::Global(index) => self.emit(Instruction::DefineGlobal(index), span),
+        };
+        Ok(())
+    }
+
+    /// Opens a scope whose slots start right after those the enclosing
+    /// scope has handed out so far. A scope closed earlier may have used
+    /// them, but its names are gone by now.
+    fn begin_scope(&mut self) {
+        let base = self.state.scopes.last().map_or(0, |scope| scope.base + scope.width);
+        self.state.scopes.push(Scope { base, width: 0 });
+    }
+
+    /// Closes the innermost scope, emptying its slots so what they held
+    /// stops counting against the memory limit.
+    fn end_scope(&mut self, span: Span) {
+        let scope = self.state.scopes.pop().expect("scopes are balanced");
+        if scope.width > 0 {
+            // Both fit: binding() already checked the scope's last slot
+            let from = scope.base as u16;
+            let count = scope.width as u16;
+            self.emit(Instruction::ClearLocals { from, count }, span);
+        }
+    }
+
+    /// Compiles statements so that they leave exactly one value, the value
+    /// of the last statement, on the stack.
+    fn sequence(&mut self, statements: &[Ast], span: Span) -> Result<(), LoveError> {
+        if statements.is_empty() {
+            self.emit(Instruction::Null, span);
+        }
+        for (i, stmt) in statements.iter().enumerate() {
+            self.statement(stmt)?;
+            if i + 1 < statements.len() {
+                self.emit(Instruction::Pop, stmt.span());
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles any node so that it leaves exactly one value on the stack.
+    fn statement(&mut self, ast: &Ast) -> Result<(), LoveError> {
+        match ast {
+            Ast::Program { statements, span } => self.sequence(statements, *span)?,
+            Ast::Block { statements, span } => {
+                self.begin_scope();
+                self.sequence(statements, *span)?;
+                self.end_scope(*span);
+            }
+            Ast::VariableDecl { name, initializer, slot, span, .. } => {
+                self.statement(initializer)?;
+                self.define(name, *slot, *span)?;
+            }
+            Ast::FunctionDecl { name, params, body, slot, span, .. } => {
+                let function = self.function(name, params.iter().map(|(p, _, _)| p.clone()).collect(), body, *span)?;
+                let index = self.constant(Value::Compiled(Rc::new(function)));
+                self.emit(Instruction::Constant(index), *span);
+                self.define(name, *slot, *span)?;
+            }
+            Ast::Call { callee, arguments, slot, span } => {
+                let argc = u8::try_from(arguments.len()).map_err(|_| LoveError::Runtime {
+                    message: "Too many arguments for one devotion".to_string(),
+                    span: Some(*span),
+                })?;
+                let name = self.name(callee);
+                let slot = match self.binding(callee, *slot, *span)? {
+                    Binding::Local(slot) => Some(slot),
+                    Binding::Global(_) => None,
+                };
+                self.emit(Instruction::GetCallee { name, slot }, *span);
+                self.emit(Instruction::PrepareCall { name, argc }, *span);
+                for argument in arguments {
+                    self.statement(argument)?;
+                }
+                self.emit(Instruction::Call { name, argc }, *span);
+            }
+            Ast::If { condition, then_branch, else_branch, span } => {
+                self.statement(condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0), condition.span());
+                self.sequence(then_branch, *span)?;
+                let to_end = self.emit(Instruction::Jump(0), *span);
+                self.patch(to_else);
+                match else_branch {
+                    Some(statements) => self.sequence(statements, *span)?,
+                    None => {
+                        self.emit(Instruction::Null, *span);
+                    }
+                }
+                self.patch(to_end);
+            }
+            Ast::While { condition, body, span } => {
+                let start = self.here();
+                self.statement(condition)?;
+                let to_exit = self.emit(Instruction::JumpIfFalse(0), condition.span());
+                self.sequence(body, *span)?;
+                self.emit(Instruction::Pop, *span);
+                self.emit(Instruction::Jump(start), *span);
+                self.patch(to_exit);
+                self.emit(Instruction::Null, *span);
+            }
+            Ast::ExpressionStmt { expr, .. } | Ast::Grouping { expr, .. } => self.statement(expr)?,
+            Ast::PrintStmt { value, span } => {
+                self.statement(value)?;
+                self.emit(Instruction::Print, *span);
+            }
+            Ast::ReturnStmt { value, span } => {
+                match value {
+                    Some(value) => self.statement(value)?,
+                    None => {
+                        self.emit(Instruction::Null, *span);
+                    }
+                }
+                self.emit(Instruction::Return, *span);
+            }
+            Ast::Binary { left, operator: BinaryOp::And, right, span } => {
+                self.statement(left)?;
+                let to_end = self.emit(Instruction::JumpIfFalseOrPop(0), *span);
+                self.statement(right)?;
+                self.emit(Instruction::CheckFeeling, *span);
+                self.patch(to_end);
+            }
+            Ast::Binary { left, operator: BinaryOp::Or, right, span } => {
+                self.statement(left)?;
+                let to_end = self.emit(Instruction::JumpIfTrueOrPop(0), *span);
+                self.statement(right)?;
+                self.emit(Instruction::CheckFeeling, *span);
+                self.patch(to_end);
+            }
+            Ast::Binary { left, operator, right, span } => {
+                self.statement(left)?;
+                self.statement(right)?;
+                self.emit(Instruction::Binary(operator.clone()), *span);
+            }
+            Ast::Unary { operator, operand, span } => {
+                self.statement(operand)?;
+                self.emit(Instruction::Unary(operator.clone()), *span);
+            }
+            Ast::Assign { name, value, slot, span } => {
+                self.statement(value)?;
+                match self.binding(name, *slot, *span)? {
+                    Binding::Local(slot) => {
+                        let name = self.name(name);
+                        self.emit(Instruction::SetLocal { slot, name }, *span)
+                    }
+                    Binding::Global(index) => self.emit(Instruction::SetGlobal(index), *span),
+                };
+            }
+            Ast::Variable { name, slot, span } => {
+                match self.binding(name, *slot, *span)? {
+                    Binding::Local(slot) => {
+                        let name = self.name(name);
+                        self.emit(Instruction::GetLocal { slot, name }, *span)
+                    }
+                    Binding::Global(index) => self.emit(Instruction::GetGlobal(index), *span),
+                };
+            }
+            Ast::Literal { value, span } => {
+                let index = self.constant(value.clone());
+                self.emit(Instruction::Constant(index), *span);
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles a devotion body into its own function. Parameters take the
+    /// first local slots, in order, and the body's own names follow them.
+    fn function(&mut self, name: &str, params: Vec<String>, body: &[Ast], span: Span) -> Result<Function, LoveError> {
+        let width = params.len();
+        let enclosing = std::mem::replace(&mut self.state, FunctionState::new(name, params));
+        self.state.scopes.push(Scope { base: 0, width });
+        self.state.slot_count = width;
+        let compiled = self.sequence(body, span);
+        self.emit(Instruction::Return, span);
+        let function = std::mem::replace(&mut self.state, enclosing);
+        compiled.map(|_| function.finish())
+    }
+}
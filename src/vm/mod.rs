@@ -0,0 +1,388 @@
+//! A bytecode compiler and stack VM for love stories, an alternative to the
+//! tree-walking interpreter. Both engines produce the same output and the
+//! same errors; the VM is simply faster on loops and recursive devotions.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::builtins::{Builtin, Context};
+use crate::error::{LoveError, TraceFrame};
+use crate::interpreter::{operators, Budget, CancelHandle, Limits};
+use crate::parser::ast::Ast;
+use crate::resolver::Resolver;
+use crate::shared_types::Value;
+use crate::span::Span;
+
+pub use chunk::{Chunk, Function, Instruction};
+pub use compiler::compile;
+
+mod chunk;
+mod compiler;
+
+/// An active devotion call.
+struct Frame {
+    function: Rc<Function>,
+    ip: usize,
+    /// Index of the frame's first slot in `locals`.
+    base: usize,
+    /// Height of the value stack when the call started, below the callee.
+    stack_base: usize,
+    call_site: Span,
+    callee: String,
+}
+
+pub struct Vm {
+    resolver: Resolver,
+    globals: HashMap<String, Value>,
+    stack: Vec<Value>,
+    /// Local slots of every active frame. A slot is empty until its
+    /// declaration runs.
+    locals: Vec<Option<Value>>,
+    frames: Vec<Frame>,
+    output: Box<dyn Write>,
+    limits: Limits,
+    budget: Budget,
+    cancel: CancelHandle,
+    context: Context,
+    bytes: usize,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::with_output(Box::new(io::stdout()))
+    }
+
+    /// Creates a VM whose `whisper` statements write to `output` instead of
+    /// stdout.
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let globals = Builtin::ALL.into_iter()
+            .map(|builtin| (builtin.name().to_string(), Value::Builtin(builtin)))
+            .collect();
+        Vm {
+            resolver: Resolver::new(),
+            globals,
+            stack: Vec::new(),
+            locals: Vec::new(),
+            frames: Vec::new(),
+            output,
+            limits: Limits::default(),
+            budget: Budget::default(),
+            cancel: CancelHandle::default(),
+            context: Context::default(),
+            bytes: 0,
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// A handle that can cancel whatever this VM is running, from any thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Redirects `whisper` output, returning the previous sink.
+    pub fn set_output(&mut self, output: Box<dyn Write>) -> Box<dyn Write> {
+        std::mem::replace(&mut self.output, output)
+    }
+
+    /// Sets the arguments stories read with `letters()` and `letter(n)`.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.context.args = args;
+    }
+
+    /// Whether stories may read environment variables with `secret(name)`.
+    /// Off by default.
+    pub fn set_allow_env(&mut self, allowed: bool) {
+        self.context.allow_env = allowed;
+    }
+
+    /// Resolves, compiles and runs a parsed love story, returning the value
+    /// of its last statement. Globals persist between calls.
+    pub fn interpret(&mut self, mut ast: Ast) -> Result<Value, LoveError> {
+        self.resolver.resolve(&mut ast)?;
+        let script = compile(&ast)?;
+        self.run(Rc::new(script))
+    }
+
+    /// Runs an already compiled story.
+    pub fn run(&mut self, script: Rc<Function>) -> Result<Value, LoveError> {
+        self.budget = Budget::start(&self.limits);
+        let stack_base = self.stack.len();
+        self.enter(script, Vec::new(), stack_base, Span::default(), String::new());
+        let result = self.execute();
+        if result.is_err() {
+            // Unwind whatever was running when the story failed
+            while let Some(frame) = self.frames.pop() {
+                self.leave(&frame);
+            }
+            self.stack.truncate(stack_base);
+        }
+        result
+    }
+
+    /// Pushes a frame whose first slots hold `arguments`.
+    fn enter(&mut self, function: Rc<Function>, arguments: Vec<Value>, stack_base: usize, call_site: Span, callee: String) {
+        let base = self.locals.len();
+        self.bytes += arguments.iter().map(Value::approx_size).sum::<usize>();
+        self.locals.extend(arguments.into_iter().map(Some));
+        self.locals.resize(base + function.slot_count, None);
+        self.frames.push(Frame { function, ip: 0, base, stack_base, call_site, callee });
+    }
+
+    /// Releases the slots of a frame that has just been popped.
+    fn leave(&mut self, frame: &Frame) {
+        let held: usize = self.locals[frame.base..].iter().flatten().map(Value::approx_size).sum();
+        self.bytes = self.bytes.saturating_sub(held);
+        self.locals.truncate(frame.base);
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("the VM always runs inside a frame")
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("compiled code never underflows the stack")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("compiled code never underflows the stack")
+    }
+
+    fn name(&self, index: u32) -> String {
+        self.frame().function.chunk.name(index).to_string()
+    }
+
+    fn constant(&self, index: u32) -> Value {
+        self.frame().function.chunk.constants[index as usize].clone()
+    }
+
+    /// Stores `value` in a local slot of the current frame, keeping the
+    /// memory count in step.
+    fn store(&mut self, slot: u16, value: Value) -> Result<(), LoveError> {
+        let index = self.frame().base + slot as usize;
+        let replaced = self.locals[index].as_ref().map_or(0, Value::approx_size);
+        self.limits.reserve(self.bytes, value.approx_size().saturating_sub(replaced))?;
+        self.bytes = (self.bytes + value.approx_size()).saturating_sub(replaced);
+        self.locals[index] = Some(value);
+        Ok(())
+    }
+
+    /// The value in a local slot of the current frame, if its declaration
+    /// has run.
+    fn local(&self, slot: u16) -> Option<&Value> {
+        self.locals[self.frame().base + slot as usize].as_ref()
+    }
+
+    fn execute(&mut self) -> Result<Value, LoveError> {
+        loop {
+            let (instruction, span) = {
+                let frame = self.frames.last_mut().expect("the VM always runs inside a frame");
+                let ip = frame.ip;
+                frame.ip += 1;
+                (frame.function.chunk.code[ip].clone(), frame.function.chunk.spans[ip])
+            };
+            match self.step(instruction, span) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                Err(error) => return Err(self.fail(error, span)),
+            }
+        }
+    }
+
+    /// Gives an error its span and, inside devotions, the love trace.
+    fn fail(&self, error: LoveError, span: Span) -> LoveError {
+        let error = error.with_span(span);
+        let trace: Vec<TraceFrame> = self.frames[1..].iter().rev()
+            .map(|frame| TraceFrame { function: frame.callee.clone(), call_site: frame.call_site })
+            .collect();
+        if trace.is_empty() || matches!(error, LoveError::Traced { .. } | LoveError::Goodbye { .. }) {
+            error
+        } else {
+            LoveError::Traced { error: Box::new(error), trace }
+        }
+    }
+
+    /// Executes one instruction, yielding the story's result once the
+    /// top-level frame returns.
+    fn step(&mut self, instruction: Instruction, span: Span) -> Result<Option<Value>, LoveError> {
+        self.budget.tick(&self.limits, &self.cancel, span)?;
+        match instruction {
+            Instruction::Constant(index) => {
+                let value = self.constant(index);
+                self.stack.push(value);
+            }
+            Instruction::Null => self.stack.push(Value::Null),
+            Instruction::Pop => {
+                self.pop();
+            }
+            Instruction::DefineGlobal(index) => {
+                let name = self.name(index);
+                let value = self.peek().clone();
+                self.limits.reserve(self.bytes, value.approx_size())?;
+                self.bytes += value.approx_size();
+                if let Some(old) = self.globals.insert(name, value) {
+                    self.bytes = self.bytes.saturating_sub(old.approx_size());
+                }
+            }
+            Instruction::GetGlobal(index) => {
+                let name = self.name(index);
+                let value = self.globals.get(&name).cloned()
+                    .ok_or(LoveError::UndefinedVariable { name, span: None })?;
+                self.stack.push(value);
+            }
+            Instruction::SetGlobal(index) => {
+                let name = self.name(index);
+                let value = self.peek().clone();
+                let replaced = self.globals.get(&name)
+                    .ok_or_else(|| LoveError::UndefinedVariable { name: name.clone(), span: None })?
+                    .approx_size();
+                self.limits.reserve(self.bytes, value.approx_size().saturating_sub(replaced))?;
+                self.bytes = (self.bytes + value.approx_size()).saturating_sub(replaced);
+                self.globals.insert(name, value);
+            }
+            Instruction::DefineLocal(slot) => {
+                let value = self.peek().clone();
+                self.store(slot, value)?;
+            }
+            Instruction::GetLocal { slot, name } => {
+                let value = self.local(slot).cloned()
+                    .ok_or_else(|| LoveError::UndefinedVariable { name: self.name(name), span: None })?;
+                self.stack.push(value);
+            }
+            Instruction::SetLocal { slot, name } => {
+                if self.local(slot).is_none() {
+                    return Err(LoveError::UndefinedVariable { name: self.name(name), span: None });
+                }
+                let value = self.peek().clone();
+                self.store(slot, value)?;
+            }
+            Instruction::ClearLocals { from, count } => {
+                let start = self.frame().base + from as usize;
+                for local in &mut self.locals[start..start + count as usize] {
+                    if let Some(value) = local.take() {
+                        self.bytes = self.bytes.saturating_sub(value.approx_size());
+                    }
+                }
+            }
+            Instruction::GetCallee { name, slot } => {
+                let value = match slot {
+                    Some(slot) => self.local(slot),
+                    None => self.globals.get(self.frame().function.chunk.name(name)),
+                };
+                let value = value.cloned()
+                    .ok_or_else(|| LoveError::UndefinedFunction { name: self.name(name), span: None })?;
+                self.stack.push(value);
+            }
+            Instruction::Binary(operator) => {
+                let right = self.pop();
+                let left = self.pop();
+                if let Some(size) = operators::allocation_size(&left, &operator, &right) {
+                    self.limits.reserve(self.bytes, size)?;
+                }
+                let value = operators::binary(left, &operator, right)?;
+                self.stack.push(value);
+            }
+            Instruction::Unary(operator) => {
+                let operand = self.pop();
+                let value = operators::unary(&operator, operand)?;
+                self.stack.push(value);
+            }
+            Instruction::CheckFeeling => {
+                operators::feeling(self.peek())?;
+            }
+            Instruction::Print => {
+                let value = self.pop();
+                writeln!(self.output, "{}", value).map_err(|e| LoveError::io("whisper", e))?;
+                self.stack.push(Value::Null);
+            }
+            Instruction::Jump(target) => self.jump(target),
+            Instruction::JumpIfFalse(target) => match self.pop() {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => self.jump(target),
+                other => return Err(LoveError::NonFeelingCondition { found: other.get_type(), span: None }),
+            },
+            Instruction::JumpIfFalseOrPop(target) => {
+                if operators::feeling(self.peek())? {
+                    self.pop();
+                } else {
+                    self.jump(target);
+                }
+            }
+            Instruction::JumpIfTrueOrPop(target) => {
+                if operators::feeling(self.peek())? {
+                    self.jump(target);
+                } else {
+                    self.pop();
+                }
+            }
+            Instruction::PrepareCall { name, argc } => match self.peek() {
+                Value::Compiled(function) if function.params.len() != argc as usize => {
+                    return Err(LoveError::ArityMismatch {
+                        name: self.name(name),
+                        expected: function.params.len(),
+                        got: argc as usize,
+                        span: None,
+                    });
+                }
+                Value::Builtin(builtin) if builtin.params().len() != argc as usize => {
+                    return Err(LoveError::ArityMismatch {
+                        name: self.name(name),
+                        expected: builtin.params().len(),
+                        got: argc as usize,
+                        span: None,
+                    });
+                }
+                Value::Compiled(_) | Value::Builtin(_) => {}
+                _ => return Err(LoveError::NotCallable { name: self.name(name), span: None }),
+            },
+            Instruction::Call { name, argc } => {
+                if self.frames.len() > self.limits.max_call_depth {
+                    return Err(LoveError::CallDepthExceeded { limit: self.limits.max_call_depth, span: None });
+                }
+                let arguments = self.stack.split_off(self.stack.len() - argc as usize);
+                let function = match self.pop() {
+                    Value::Compiled(function) => function,
+                    Value::Builtin(builtin) => {
+                        let result = builtin.call(arguments, &self.context)?;
+                        self.stack.push(result);
+                        return Ok(None);
+                    }
+                    _ => return Err(LoveError::NotCallable { name: self.name(name), span: None }),
+                };
+                let callee = self.name(name);
+                let stack_base = self.stack.len();
+                self.enter(function, arguments, stack_base, span, callee);
+            }
+            Instruction::Return => {
+                let result = self.pop();
+                let frame = self.frames.pop().expect("the VM always runs inside a frame");
+                self.leave(&frame);
+                self.stack.truncate(frame.stack_base);
+                if self.frames.is_empty() {
+                    return Ok(Some(result));
+                }
+                self.stack.push(result);
+            }
+        }
+        Ok(None)
+    }
+
+    fn jump(&mut self, target: u32) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = target as usize;
+        }
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,35 @@
+//! The devotions built into every love story.
+
+mod common;
+
+use common::{run, run_with, Setup, ENGINES};
+
+#[test]
+fn secret_is_refused_unless_allowed() {
+    for engine in ENGINES {
+        let outcome = run(engine, "whisper secret(\"PATH\");");
+        assert_eq!(outcome.code(), Some("E0401"), "{:?}", engine);
+        assert_eq!(outcome.output, "");
+    }
+}
+
+#[test]
+fn secret_reads_the_environment_when_allowed() {
+    let setup = Setup { allow_env: true, ..Setup::default() };
+    let expected = std::env::var("PATH").expect("tests run with a PATH");
+    for engine in ENGINES {
+        let outcome = run_with(engine, "whisper secret(\"PATH\");", &setup);
+        assert_eq!(outcome.output, format!("{}\n", expected), "{:?}", engine);
+        let outcome = run_with(engine, "whisper secret(\"LOVE_SURELY_NOT_SET\");", &setup);
+        assert_eq!(outcome.output, "lonely\n", "{:?}", engine);
+    }
+}
+
+#[test]
+fn letters_reads_the_story_arguments() {
+    let setup = Setup { args: vec!["romeo".into(), "juliet".into()], ..Setup::default() };
+    for engine in ENGINES {
+        let outcome = run_with(engine, "whisper letters(); whisper letter(1); whisper letter(2);", &setup);
+        assert_eq!(outcome.output, "2\njuliet\nlonely\n", "{:?}", engine);
+    }
+}
@@ -0,0 +1,67 @@
+//! Type errors found before a love story runs.
+
+mod common;
+
+use common::parse;
+use love_language::checker::{check, declared_types};
+use love_language::error::LoveError;
+use love_language::shared_types::Type;
+
+fn codes(source: &str) -> Vec<&'static str> {
+    match check(&parse(source)) {
+        Ok(()) => Vec::new(),
+        Err(e) => e.errors().into_iter().map(LoveError::code).collect(),
+    }
+}
+
+#[test]
+fn operators_on_the_wrong_kinds_of_values() {
+    assert_eq!(codes("whisper 1 kiss \"two\";"), ["E0301"]);
+    assert_eq!(codes("whisper yes breakup 1;"), ["E0301"]);
+    assert_eq!(codes("heart x match \"a\"; whisper x admires 1;"), ["E0301"]);
+    assert!(codes("whisper \"a\" cuddle \"b\";").is_empty());
+}
+
+#[test]
+fn conditions_must_be_feelings() {
+    assert_eq!(codes("crush (1) { whisper 1; }"), ["E0302"]);
+    assert_eq!(codes("dating (\"always\") { goodbye(0); }"), ["E0302"]);
+    assert!(codes("heart x match 1; crush (x soulmate 1) { whisper x; }").is_empty());
+}
+
+#[test]
+fn calls_to_devotions() {
+    assert_eq!(codes("devotion f(a: number) { } f();"), ["E0405"]);
+    assert_eq!(codes("devotion f(a: number) { } f(\"one\");"), ["E0301"]);
+    assert_eq!(codes("heart x match 1; x();"), ["E0404"]);
+    assert!(codes("devotion f(a: number) { } f(1);").is_empty());
+}
+
+#[test]
+fn promises_keep_the_declared_return_type() {
+    assert_eq!(codes("devotion f() -> number { promise \"one\"; }"), ["E0301"]);
+    assert_eq!(codes("devotion f() -> number { promise 1; } whisper f() cuddle yes;"), ["E0301"]);
+    assert!(codes("devotion f() -> text { promise \"one\"; } whisper f() cuddle \"!\";").is_empty());
+}
+
+#[test]
+fn only_certain_mistakes_are_reported() {
+    // x holds a number, then text, so nothing is known about it
+    assert!(codes("heart x match 1; x match \"a\"; whisper x kiss 2;").is_empty());
+    // f might be rebound, so calls to it aren't checked
+    assert!(codes("devotion f(a: number) { } heart g match 1; f match g; f();").is_empty());
+}
+
+#[test]
+fn every_mistake_is_reported() {
+    assert_eq!(codes("whisper 1 kiss yes;\ncrush (2) { }\nwhisper \"a\" split 2;"), ["E0301", "E0302", "E0301"]);
+}
+
+#[test]
+fn declared_types_are_known_by_where_they_start() {
+    let source = "heart n match 1;\nforever NAME match \"amor\";\nheart changes match 1;\nchanges match yes;";
+    let types = declared_types(&parse(source));
+    assert_eq!(types.get(&0), Some(&Type::Number));
+    assert_eq!(types.get(&source.find("forever").unwrap()), Some(&Type::Text));
+    assert_eq!(types.get(&source.find("heart changes").unwrap()), None);
+}
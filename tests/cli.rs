@@ -0,0 +1,204 @@
+//! The `love` command, run the way scripts and CI run it.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs `love` with its output captured, which makes it plain.
+fn love(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_love"))
+        .args(args)
+        .output()
+        .expect("love runs")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn whispers_go_to_stdout_and_diagnostics_to_stderr() {
+    let output = love(&["-e", "whisper \"hi\"; whisper 1 split 0;"]);
+    assert_eq!(stdout(&output), "hi\n");
+    assert!(stderr(&output).starts_with("error[E0406]: Cannot split by zero!"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn parse_errors_go_to_stderr() {
+    let output = love(&["check", "-e", "heart a match 1\nheart b match 2\n"]);
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output).matches("error[E0201]").count(), 2, "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn lints_go_to_stderr() {
+    let output = love(&["lint", "-e", "heart unused match 1;"]);
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).starts_with("warning[unused-heart]"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn checks_that_find_problems_exit_with_7() {
+    let unformatted = love(&["fmt", "--check", "-e", "heart   x match 1;"]);
+    assert_eq!(unformatted.status.code(), Some(7));
+    let formatted = love(&["fmt", "--check", "-e", "heart x match 1;\n"]);
+    assert_eq!(formatted.status.code(), Some(0), "{}", stdout(&formatted));
+
+    let denied = love(&["lint", "-e", "heart i match 0; dating (i envies 3) { whisper i; }"]);
+    assert_eq!(denied.status.code(), Some(7));
+    let warned = love(&["lint", "-A", "stuck-dating", "-e", "heart i match 0; dating (i envies 3) { whisper i; }"]);
+    assert_eq!(warned.status.code(), Some(0));
+}
+
+#[test]
+fn exit_status_tells_the_kind_of_error() {
+    let cases: [(&[&str], i32); 7] = [
+        (&["-e", "whisper 1;"], 0),
+        (&["-e", "whisper 1 split 0;"], 1),
+        (&["--no-such-option"], 2),
+        (&["-e", "whisper 1 @ 2;"], 3),
+        (&["-e", "whisper 1"], 4),
+        (&["-e", "{ whisper x; heart x match 1; }"], 5),
+        (&["check", "-e", "whisper 1 kiss \"two\";"], 6),
+    ];
+    for (args, status) in cases {
+        assert_eq!(love(args).status.code(), Some(status), "{:?}", args);
+    }
+    assert_eq!(love(&["-e", "goodbye(42);"]).status.code(), Some(42));
+}
+
+#[test]
+fn the_default_call_depth_fits_on_the_stack() {
+    let down = "devotion down(n: number) -> number {
+        crush (n soulmate 0) { promise 0; }
+        promise 1 cuddle down(n breakup 1);
+    }";
+    for engine in [&[][..], &["--vm"][..]] {
+        let code = format!("{} whisper down(199);", down);
+        let output = love(&[engine, &["-e", &code]].concat());
+        assert_eq!(stdout(&output), "199\n", "{:?}: {}", engine, stderr(&output));
+        assert_eq!(output.status.code(), Some(0));
+
+        let code = format!("{} whisper down(500);", down);
+        let output = love(&[engine, &["-e", &code]].concat());
+        assert!(stderr(&output).starts_with("error[E0415]"), "{:?}: {}", engine, stderr(&output));
+        assert_eq!(output.status.code(), Some(1));
+    }
+}
+
+#[test]
+fn stories_come_from_the_command_line() {
+    let output = love(&["-e", "whisper \"inline\";"]);
+    assert_eq!(stdout(&output), "inline\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(love(&["check", "-e", "whisper 1"]).status.code(), Some(4));
+    assert_eq!(love(&["-e", "goodbye(3);"]).status.code(), Some(3));
+}
+
+#[test]
+fn the_same_seed_picks_the_same_flavor() {
+    let story = "whisper \"hi\"; whisper 1 split 0;";
+    let first = love(&["--no-plain", "--seed", "7", "-e", story]);
+    let second = love(&["--no-plain", "--seed", "7", "-e", story]);
+    assert!(stdout(&first).contains("Love story output"), "{}", stdout(&first));
+    assert_eq!(first.stdout, second.stdout);
+    assert_eq!(first.stderr, second.stderr);
+
+    let from_env = Command::new(env!("CARGO_BIN_EXE_love"))
+        .args(["--no-plain", "-e", story])
+        .env("LOVE_SEED", "7")
+        .output()
+        .expect("love runs");
+    assert_eq!(from_env.stdout, first.stdout);
+    assert_eq!(from_env.stderr, first.stderr);
+
+    let output = love(&["check", "--no-plain", "--seed", "7", "-e", "whisper 1;"]);
+    assert_eq!(output.stdout, love(&["check", "--no-plain", "--seed", "7", "-e", "whisper 1;"]).stdout);
+}
+
+#[test]
+fn no_flavor_leaves_out_the_footer() {
+    let story = "whisper 1 split 0;";
+    let flavored = stderr(&love(&["--no-plain", "--seed", "7", "-e", story]));
+    let plain = stderr(&love(&["--no-plain", "--no-flavor", "-e", story]));
+    assert!(flavored.contains("error[E0406]"), "{}", flavored);
+    assert!(plain.contains("error[E0406]"), "{}", plain);
+    let footer = love_language::fun::ERROR_MESSAGES.iter().find(|message| flavored.contains(*message));
+    assert!(footer.is_some(), "{}", flavored);
+    assert!(!love_language::fun::ERROR_MESSAGES.iter().any(|message| plain.contains(message)), "{}", plain);
+    assert!(plain.trim_end().ends_with("check the divisor before you split"), "{:?}", plain);
+}
+
+#[test]
+fn seeds_must_be_whole_numbers() {
+    let output = love(&["--seed", "soon", "-e", "whisper 1;"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = Command::new(env!("CARGO_BIN_EXE_love"))
+        .args(["-e", "whisper 1;"])
+        .env("LOVE_SEED", "soon")
+        .output()
+        .expect("love runs");
+    assert!(stderr(&output).contains("LOVE_SEED must be a whole number"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn highlight_prints_json_classes() {
+    let output = love(&["highlight", "-e", "devotion f() { } f(); // done"]);
+    assert_eq!(output.status.code(), Some(0));
+    let classes: serde_json::Value = serde_json::from_str(&stdout(&output)).expect("highlight prints JSON");
+    let classes: Vec<(&str, &str)> = classes.as_array().unwrap().iter()
+        .map(|h| (h["text"].as_str().unwrap(), h["class"].as_str().unwrap()))
+        .collect();
+    assert_eq!(classes, [
+        ("devotion", "keyword"),
+        ("f", "function"),
+        ("(", "punctuation"),
+        (")", "punctuation"),
+        ("{", "punctuation"),
+        ("}", "punctuation"),
+        ("f", "function"),
+        ("(", "punctuation"),
+        (")", "punctuation"),
+        (";", "punctuation"),
+        ("// done", "comment"),
+    ]);
+
+    let output = love(&["highlight", "-e", "whisper 1 @ 2;"]);
+    assert!(stderr(&output).starts_with("error[E0101]"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn a_separator_after_the_story_is_not_an_argument() {
+    let story = "whisper letters(); whisper letter(0);";
+    let output = love(&["-e", story, "--", "--vm", "two"]);
+    assert_eq!(stdout(&output), "2\n--vm\n");
+    let output = love(&["-e", story, "--", "--", "two"]);
+    assert_eq!(stdout(&output), "2\n--\n");
+    let output = love(&["run", "-e", story, "one", "--"]);
+    assert_eq!(stdout(&output), "2\none\n");
+}
+
+#[test]
+fn stories_come_from_stdin_or_love_files() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_love"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("love runs");
+    child.stdin.take().unwrap().write_all(b"whisper \"from stdin\";").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(stdout(&output), "from stdin\n");
+
+    let output = love(&["Cargo.toml"]);
+    assert!(stderr(&output).starts_with("error[E0408]"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,75 @@
+//! Shared pieces of the integration tests: parsing love stories and running
+//! them on either engine with their output captured.
+
+#![allow(dead_code)]
+
+use love_language::error::LoveError;
+use love_language::interpreter::{Interpreter, Limits, SharedBuffer};
+use love_language::lexer::Lexer;
+use love_language::parser::ast::Ast;
+use love_language::parser::Parser;
+use love_language::runner::Engine;
+use love_language::shared_types::Value;
+use love_language::vm::Vm;
+
+pub const ENGINES: [Engine; 2] = [Engine::TreeWalker, Engine::Vm];
+
+pub fn parse(source: &str) -> Ast {
+    let tokens = Lexer::new(source).tokenize().expect("test source lexes");
+    Parser::new(tokens).parse().expect("test source parses")
+}
+
+/// How an engine is set up before running a story.
+#[derive(Debug, Clone, Default)]
+pub struct Setup {
+    pub limits: Limits,
+    pub args: Vec<String>,
+    pub allow_env: bool,
+}
+
+/// What running a love story did: what it whispered and how it ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub output: String,
+    pub result: Result<Value, LoveError>,
+}
+
+impl Outcome {
+    /// The code of the error the story failed with, if it failed.
+    pub fn code(&self) -> Option<&'static str> {
+        self.result.as_ref().err().map(LoveError::code)
+    }
+}
+
+pub fn run(engine: Engine, source: &str) -> Outcome {
+    run_with(engine, source, &Setup::default())
+}
+
+pub fn run_with(engine: Engine, source: &str, setup: &Setup) -> Outcome {
+    execute(engine, parse(source), setup)
+}
+
+pub fn run_ast(engine: Engine, ast: Ast) -> Outcome {
+    execute(engine, ast, &Setup::default())
+}
+
+fn execute(engine: Engine, ast: Ast, setup: &Setup) -> Outcome {
+    let buffer = SharedBuffer::new();
+    let result = match engine {
+        Engine::TreeWalker => {
+            let mut interpreter = Interpreter::with_output(Box::new(buffer.clone()));
+            interpreter.set_limits(setup.limits.clone());
+            interpreter.set_args(setup.args.clone());
+            interpreter.set_allow_env(setup.allow_env);
+            interpreter.interpret(ast)
+        }
+        Engine::Vm => {
+            let mut vm = Vm::with_output(Box::new(buffer.clone()));
+            vm.set_limits(setup.limits.clone());
+            vm.set_args(setup.args.clone());
+            vm.set_allow_env(setup.allow_env);
+            vm.interpret(ast)
+        }
+    };
+    Outcome { output: buffer.contents(), result }
+}
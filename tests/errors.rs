@@ -0,0 +1,151 @@
+//! Error codes, kinds and how errors are reported.
+
+mod common;
+
+use common::{run, ENGINES};
+use love_language::diagnostic::render_error;
+use love_language::error::{ErrorKind, LoveError};
+use love_language::lexer::Lexer;
+use love_language::parser::Parser;
+use love_language::runner::Engine;
+
+fn lex_error(source: &str) -> LoveError {
+    Lexer::new(source).tokenize().expect_err("source fails to lex")
+}
+
+fn parse_error(source: &str) -> LoveError {
+    let tokens = Lexer::new(source).tokenize().expect("source lexes");
+    Parser::new(tokens).parse().expect_err("source fails to parse")
+}
+
+fn runtime_error(source: &str) -> LoveError {
+    run(Engine::TreeWalker, source).result.expect_err("source fails to run")
+}
+
+fn render(error: &LoveError, source: &str) -> String {
+    colored::control::set_override(false);
+    render_error(error, source, "story.love", None)
+}
+
+#[test]
+fn lexer_errors() {
+    let error = lex_error("heart x match 1;\nwhisper x @ 2;");
+    assert_eq!(error.kind(), ErrorKind::Lexer);
+    assert_eq!(error.code(), "E0101");
+    assert_eq!(error.exit_code(), 3);
+    let span = error.span().unwrap();
+    assert_eq!((span.line, span.column), (2, 11));
+    assert!(error.hint().is_some());
+}
+
+#[test]
+fn parser_errors() {
+    let error = parse_error("heart x match 1\nwhisper x;");
+    assert_eq!(error.kind(), ErrorKind::Parser);
+    assert_eq!(error.code(), "E0201");
+    assert_eq!(error.exit_code(), 4);
+    assert_eq!(error.hint(), Some("every love statement ends with a ';'"));
+
+    assert_eq!(parse_error("1 match 2;").code(), "E0202");
+}
+
+#[test]
+fn runtime_error_codes() {
+    let cases = [
+        ("whisper nobody;", "E0402", ErrorKind::Runtime),
+        ("nobody();", "E0403", ErrorKind::Runtime),
+        ("heart x match 1; x();", "E0404", ErrorKind::Runtime),
+        ("devotion f(a: number) { } f();", "E0405", ErrorKind::Runtime),
+        ("whisper 1 split 0;", "E0406", ErrorKind::Runtime),
+        ("whisper 1 kiss \"two\";", "E0301", ErrorKind::Type),
+        ("crush (1) { whisper 1; }", "E0302", ErrorKind::Type),
+        ("goodbye(3);", "E0414", ErrorKind::Runtime),
+    ];
+    for engine in ENGINES {
+        for (source, code, kind) in cases {
+            let error = run(engine, source).result.expect_err(source);
+            assert_eq!(error.code(), code, "{:?} running {}", engine, source);
+            assert_eq!(error.kind(), kind, "{:?} running {}", engine, source);
+        }
+    }
+    assert_eq!(runtime_error("whisper 1 split 0;").exit_code(), 1);
+    assert_eq!(runtime_error("whisper 1 kiss \"two\";").exit_code(), 6);
+    assert_eq!(runtime_error("goodbye(3);").exit_code(), 3);
+}
+
+#[test]
+fn errors_inside_devotions_are_traced() {
+    let source = "devotion inner() { whisper 1 split 0; }\ndevotion outer() { inner(); }\nouter();";
+    let error = runtime_error(source);
+    assert_eq!(error.code(), "E0406");
+    let trace: Vec<&str> = error.trace().iter().map(|frame| frame.function.as_str()).collect();
+    assert_eq!(trace, ["inner", "outer"]);
+    assert_eq!(error.span().unwrap().line, 1);
+    let rendered = render(&error, source);
+    assert!(rendered.contains("  0: in devotion 'inner', called at story.love:2:20\n"), "{}", rendered);
+    assert!(rendered.contains("  1: in devotion 'outer', called at story.love:3:1\n"), "{}", rendered);
+}
+
+#[test]
+fn long_traces_are_trimmed() {
+    let source = "devotion down(n: number) {\n    crush (n envies 1) { whisper 1 split 0; }\n    down(n breakup 1);\n}\ndown(20);";
+    let error = runtime_error(source);
+    assert_eq!(error.trace().len(), 21);
+    let rendered = render(&error, source);
+    assert!(rendered.contains("  ... 13 more devotions ...\n"), "{}", rendered);
+    assert_eq!(rendered.matches("in devotion 'down'").count(), 8);
+}
+
+#[test]
+fn diagnostics_quote_the_line_and_point_at_the_problem() {
+    let source = "heart x match 1;\nwhisper x split 0;";
+    let rendered = render(&runtime_error(source), source);
+    assert_eq!(
+        rendered,
+        "error[E0406]: Cannot split by zero!
+ --> story.love:2:9
+  |
+2 | whisper x split 0;
+  |         ^^^^^^^^^ the divisor is zero
+  |
+  = hint: check the divisor before you split
+
+",
+    );
+}
+
+#[test]
+fn diagnostics_carry_hints() {
+    let source = "heart x match 1\nwhisper x;";
+    let rendered = render(&parse_error(source), source);
+    assert_eq!(
+        rendered,
+        "error[E0201]: Expected ';' after variable declaration
+ --> story.love:1:16
+  |
+1 | heart x match 1
+  |                ^ expected ';' here
+  |
+  = hint: every love statement ends with a ';'
+
+",
+    );
+}
+
+#[test]
+fn collected_errors_are_all_reported() {
+    let source = "heart a match ;\nwhisper 1;\nheart b match ;\n";
+    let error = parse_error(source);
+    assert_eq!(error.errors().len(), 2);
+    let rendered = render(&error, source);
+    assert_eq!(rendered.matches("error[E0201]").count(), 2);
+    assert!(rendered.ends_with("2 errors found in story.love\n"), "{}", rendered);
+}
+
+#[test]
+fn footers_come_last() {
+    let source = "whisper nobody;";
+    colored::control::set_override(false);
+    let rendered = render_error(&runtime_error(source), source, "story.love", Some("so sad".to_string()));
+    assert!(rendered.ends_with("\n\nso sad\n"), "{}", rendered);
+}
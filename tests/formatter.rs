@@ -0,0 +1,69 @@
+//! `love fmt`: the canonical style, comments kept where they were.
+
+mod common;
+
+use common::{run, ENGINES};
+use love_language::formatter;
+use love_language::lexer::Lexer;
+use love_language::parser::Parser;
+
+const STORIES: [&str; 5] = [
+    include_str!("../examples/minimal.love"),
+    include_str!("../examples/love_story.love"),
+    include_str!("../benches/programs/fib.love"),
+    include_str!("../benches/programs/loop.love"),
+    include_str!("../benches/programs/strings.love"),
+];
+
+fn format(source: &str) -> String {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("test source lexes");
+    let ast = Parser::new(tokens).parse().expect("test source parses");
+    formatter::format(&ast, lexer.trivia())
+}
+
+#[test]
+fn formatting_twice_changes_nothing() {
+    for story in STORIES {
+        let once = format(story);
+        assert_eq!(format(&once), once);
+    }
+}
+
+#[test]
+fn formatting_keeps_what_stories_do() {
+    for story in STORIES {
+        for engine in ENGINES {
+            let formatted = run(engine, &format(story));
+            let original = run(engine, story);
+            assert_eq!(formatted.output, original.output);
+            assert_eq!(formatted.code(), original.code());
+        }
+    }
+}
+
+#[test]
+fn canonical_style() {
+    assert_eq!(
+        format("heart   x match(1 cuddle 2)kiss 3;crush(x admires 1){whisper x;}butterflies{whisper \"no\";}"),
+        "heart x match (1 cuddle 2) kiss 3;\ncrush (x admires 1) {\n    whisper x;\n} butterflies {\n    whisper \"no\";\n}\n",
+    );
+    assert_eq!(
+        format("heart a match 1;\ndevotion f(n: number) -> number { promise n; }\nwhisper f(a);"),
+        "heart a match 1;\n\ndevotion f(n: number) -> number {\n    promise n;\n}\n\nwhisper f(a);\n",
+    );
+}
+
+#[test]
+fn comments_stay_where_they_were() {
+    let source = "// the beginning\nheart x match 1; // first love\n{\n    // inside\n    whisper x;\n}\n// the end\n";
+    assert_eq!(format(source), source);
+    // One inside a statement follows it instead
+    assert_eq!(format("whisper 1 cuddle // one\n2;\nwhisper 3;\n"), "whisper 1 cuddle 2; // one\nwhisper 3;\n");
+}
+
+#[test]
+fn blank_lines_are_squeezed() {
+    assert_eq!(format("whisper 1;\n\n\n\nwhisper 2;\n"), "whisper 1;\n\nwhisper 2;\n");
+    assert_eq!(format("{\n\n    whisper 1;\n\n}\n"), "{\n    whisper 1;\n}\n");
+}
@@ -0,0 +1,90 @@
+//! `love highlight`: the class of every piece of a love story.
+
+use love_language::highlight::{highlight, to_json, Class};
+use serde_json::json;
+
+/// Each highlight of `source` as its text and class.
+fn classes(source: &str) -> Vec<(&str, Class)> {
+    highlight(source).expect("test source lexes")
+        .into_iter()
+        .map(|h| (&source[h.span.start..h.span.end], h.class))
+        .collect()
+}
+
+#[test]
+fn every_token_and_comment_has_a_class() {
+    let source = "devotion greet(name: text) -> text {
+    promise \"hi \" cuddle name; // warmly
+}
+heart shy match no;
+crush (not shy and 1 admires 0) { whisper greet(\"you\"); } butterflies { whisper lonely; }";
+    assert_eq!(classes(source), [
+        ("devotion", Class::Keyword),
+        ("greet", Class::Function),
+        ("(", Class::Punctuation),
+        ("name", Class::Identifier),
+        (":", Class::Punctuation),
+        ("text", Class::Type),
+        (")", Class::Punctuation),
+        ("->", Class::Punctuation),
+        ("text", Class::Type),
+        ("{", Class::Punctuation),
+        ("promise", Class::Keyword),
+        ("\"hi \"", Class::Literal),
+        ("cuddle", Class::Operator),
+        ("name", Class::Identifier),
+        (";", Class::Punctuation),
+        ("// warmly", Class::Comment),
+        ("}", Class::Punctuation),
+        ("heart", Class::Keyword),
+        ("shy", Class::Identifier),
+        ("match", Class::Operator),
+        ("no", Class::Literal),
+        (";", Class::Punctuation),
+        ("crush", Class::Keyword),
+        ("(", Class::Punctuation),
+        ("not", Class::Operator),
+        ("shy", Class::Identifier),
+        ("and", Class::Operator),
+        ("1", Class::Literal),
+        ("admires", Class::Operator),
+        ("0", Class::Literal),
+        (")", Class::Punctuation),
+        ("{", Class::Punctuation),
+        ("whisper", Class::Keyword),
+        ("greet", Class::Function),
+        ("(", Class::Punctuation),
+        ("\"you\"", Class::Literal),
+        (")", Class::Punctuation),
+        (";", Class::Punctuation),
+        ("}", Class::Punctuation),
+        ("butterflies", Class::Keyword),
+        ("{", Class::Punctuation),
+        ("whisper", Class::Keyword),
+        ("lonely", Class::Literal),
+        (";", Class::Punctuation),
+        ("}", Class::Punctuation),
+    ]);
+}
+
+#[test]
+fn calls_are_found_past_statements_that_dont_parse() {
+    let highlights = classes("whisper f(1 cuddle; whisper f(2);");
+    let names: Vec<_> = highlights.iter().filter(|(text, _)| *text == "f").map(|(_, class)| *class).collect();
+    assert_eq!(names, [Class::Identifier, Class::Function]);
+}
+
+#[test]
+fn stories_that_dont_lex_have_no_classes() {
+    assert_eq!(highlight("whisper 1 @ 2;").unwrap_err().code(), "E0101");
+}
+
+#[test]
+fn json_has_class_text_and_position() {
+    let source = "heart x match 1;\nwhisper x;";
+    let highlights = highlight(source).unwrap();
+    let json = to_json(&highlights, source);
+    assert_eq!(json.as_array().map(Vec::len), Some(8));
+    assert_eq!(json[0], json!({ "class": "keyword", "text": "heart", "line": 1, "column": 1, "start": 0, "end": 5 }));
+    assert_eq!(json[6], json!({ "class": "identifier", "text": "x", "line": 2, "column": 9, "start": 25, "end": 26 }));
+}
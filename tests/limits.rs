@@ -0,0 +1,117 @@
+//! Limits and cancellation for embedders running untrusted love stories.
+
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use common::parse;
+use love_language::error::LoveError;
+use love_language::interpreter::{Interpreter, Limits, SharedBuffer};
+use love_language::vm::Vm;
+
+const FOREVER: &str = "heart i match 0; dating (yes) { i match i cuddle 1; }";
+
+fn quiet_interpreter() -> Interpreter {
+    Interpreter::with_output(Box::new(SharedBuffer::new()))
+}
+
+fn quiet_vm() -> Vm {
+    Vm::with_output(Box::new(SharedBuffer::new()))
+}
+
+#[test]
+fn step_limit() {
+    let mut interpreter = quiet_interpreter();
+    interpreter.set_limits(Limits { max_steps: Some(1_000), ..Limits::default() });
+    let error = interpreter.interpret(parse(FOREVER)).unwrap_err();
+    assert!(matches!(error, LoveError::StepLimitExceeded { limit: 1_000, .. }), "{:?}", error);
+    assert_eq!(error.code(), "E0410");
+}
+
+#[test]
+fn time_limit() {
+    let mut interpreter = quiet_interpreter();
+    interpreter.set_limits(Limits { timeout: Some(Duration::from_millis(50)), ..Limits::default() });
+    assert_eq!(interpreter.interpret(parse(FOREVER)).unwrap_err().code(), "E0411");
+}
+
+#[test]
+fn memory_limit() {
+    let mut interpreter = quiet_interpreter();
+    interpreter.set_limits(Limits { max_memory: Some(10_000), ..Limits::default() });
+    let doubling = "heart s match \"love\"; dating (yes) { s match s cuddle s; }";
+    let error = interpreter.interpret(parse(doubling)).unwrap_err();
+    assert!(matches!(error, LoveError::MemoryLimitExceeded { limit: 10_000, .. }), "{:?}", error);
+    assert_eq!(error.code(), "E0413");
+}
+
+#[test]
+fn memory_is_given_back_when_blocks_end() {
+    let letter = "x".repeat(600);
+    let mut interpreter = quiet_interpreter();
+    interpreter.set_limits(Limits { max_memory: Some(1_000), ..Limits::default() });
+    let one_after_another = format!("{{ heart a match \"{0}\"; }} {{ heart b match \"{0}\"; }}", letter);
+    assert!(interpreter.interpret(parse(&one_after_another)).is_ok());
+    let both_at_once = format!("{{ heart a match \"{0}\"; heart b match \"{0}\"; }}", letter);
+    assert_eq!(interpreter.interpret(parse(&both_at_once)).unwrap_err().code(), "E0413");
+}
+
+#[test]
+fn cancelling_from_another_thread() {
+    let mut interpreter = quiet_interpreter();
+    let cancel = interpreter.cancel_handle();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        cancel.cancel();
+    });
+    let error = interpreter.interpret(parse(FOREVER)).unwrap_err();
+    canceller.join().unwrap();
+    assert!(matches!(error, LoveError::Cancelled { .. }), "{:?}", error);
+    assert_eq!(error.code(), "E0412");
+
+    let mut vm = quiet_vm();
+    let cancel = vm.cancel_handle();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        cancel.cancel();
+    });
+    let error = vm.interpret(parse(FOREVER)).unwrap_err();
+    canceller.join().unwrap();
+    assert_eq!(error.code(), "E0412");
+}
+
+#[test]
+fn a_cancellation_only_stops_one_run() {
+    let mut interpreter = quiet_interpreter();
+    interpreter.cancel_handle().cancel();
+    assert_eq!(interpreter.interpret(parse(FOREVER)).unwrap_err().code(), "E0412");
+    assert!(!interpreter.cancel_handle().is_cancelled());
+    assert!(interpreter.interpret(parse("whisper 1;")).is_ok());
+}
+
+#[test]
+fn every_run_gets_a_fresh_budget() {
+    let counting = "heart i match 0; dating (i envies 20) { i match i cuddle 1; }";
+    let limits = Limits { max_steps: Some(500), ..Limits::default() };
+
+    let mut interpreter = quiet_interpreter();
+    interpreter.set_limits(limits.clone());
+    let mut vm = quiet_vm();
+    vm.set_limits(limits);
+    for _ in 0..5 {
+        interpreter.interpret(parse(counting)).expect("each run fits the budget");
+        vm.interpret(parse(counting)).expect("each run fits the budget");
+    }
+    assert_eq!(interpreter.interpret(parse(FOREVER)).unwrap_err().code(), "E0410");
+    assert_eq!(vm.interpret(parse(FOREVER)).unwrap_err().code(), "E0410");
+}
+
+#[test]
+fn limits_can_be_read_back() {
+    let limits = Limits { max_steps: Some(10), timeout: Some(Duration::from_secs(1)), ..Limits::default() };
+    let mut interpreter = quiet_interpreter();
+    assert_eq!(interpreter.limits(), &Limits::default());
+    interpreter.set_limits(limits.clone());
+    assert_eq!(interpreter.limits(), &limits);
+}
@@ -0,0 +1,105 @@
+//! `love lint` rules and how their severities are configured.
+
+mod common;
+
+use common::parse;
+use love_language::diagnostic::Level;
+use love_language::linter::{lint, Lint, LintConfig, Rule, Severity};
+
+fn lints(source: &str) -> Vec<Lint> {
+    lint(&parse(source), &LintConfig::new())
+}
+
+fn rules(source: &str) -> Vec<&'static str> {
+    lints(source).iter().map(|lint| lint.rule.name()).collect()
+}
+
+#[test]
+fn unused_heart() {
+    assert_eq!(rules("heart alone match 1;"), ["unused-heart"]);
+    assert_eq!(rules("{ heart inner match 1; }"), ["unused-heart"]);
+    assert_eq!(rules("devotion f() { heart inner match 1; } f();"), ["unused-heart"]);
+    assert!(rules("heart _quiet match 1;").is_empty());
+    assert!(rules("heart read match 1; whisper read;").is_empty());
+    // Devotions may read globals declared after them
+    assert!(rules("devotion f() { whisper later; } heart later match 1; f();").is_empty());
+    assert!(rules("devotion never_called() { }").is_empty());
+}
+
+#[test]
+fn after_promise() {
+    let found = lints("devotion f() -> number { promise 1; whisper \"lost\"; }");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].rule, Rule::AfterPromise);
+    assert_eq!(found[0].span.column, 37);
+    assert_eq!(
+        rules("devotion f(a: feeling) -> number { crush (a) { promise 1; } butterflies { promise 2; } promise 3; }"),
+        ["after-promise"],
+    );
+    assert!(rules("devotion f(a: feeling) -> number { crush (a) { promise 1; } promise 2; }").is_empty());
+}
+
+#[test]
+fn always_yes() {
+    assert_eq!(rules("crush (1 admires 0) { whisper 1; }"), ["always-yes"]);
+    assert_eq!(rules("dating (yes) { goodbye(0); }"), ["always-yes"]);
+    assert!(rules("heart x match 1; crush (x admires 0) { whisper 1; }").is_empty());
+}
+
+#[test]
+fn shadowed_vow() {
+    assert_eq!(rules("devotion f(n: number) -> number { heart n match 2; promise n; }"), ["shadowed-vow"]);
+}
+
+#[test]
+fn quiet_forever() {
+    let found = lints("forever limit match 3; whisper limit;");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].rule, Rule::QuietForever);
+    assert!(found[0].message.contains("'LIMIT'"), "{}", found[0].message);
+    assert!(rules("forever LIMIT match 3; whisper LIMIT;").is_empty());
+}
+
+#[test]
+fn stuck_dating() {
+    let found = lints("heart i match 0; dating (i envies 3) { whisper i; }");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].rule, Rule::StuckDating);
+    assert_eq!(found[0].severity, Severity::Deny);
+    assert!(rules("heart i match 0; dating (i envies 3) { i match i cuddle 1; }").is_empty());
+    // A call or a promise might end the loop some other way
+    assert!(rules("heart i match 0; dating (i envies 3) { goodbye(0); }").is_empty());
+}
+
+#[test]
+fn severities_can_be_configured() {
+    let ast = parse("heart unused match 1; forever quiet match 2; whisper quiet;");
+    let mut config = LintConfig::new();
+    assert_eq!(lint(&ast, &config).len(), 2);
+
+    config.set(Rule::QuietForever, Severity::Allow);
+    let found = lint(&ast, &config);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].rule, Rule::UnusedHeart);
+    assert_eq!(found[0].diagnostic().level, Level::Warning);
+
+    config.set_all(Severity::Deny);
+    let found = lint(&ast, &config);
+    assert_eq!(found.len(), 2);
+    assert!(found.iter().all(|lint| lint.diagnostic().level == Level::Error));
+}
+
+#[test]
+fn lints_come_in_source_order() {
+    let found = lints("devotion f() { heart a match 1; }\nforever b match 1;\nheart c match 1;\nwhisper b;");
+    let lines: Vec<usize> = found.iter().map(|lint| lint.span.line).collect();
+    assert_eq!(lines, [1, 2, 3]);
+}
+
+#[test]
+fn rules_are_found_by_name() {
+    for rule in Rule::ALL {
+        assert_eq!(Rule::from_name(rule.name()), Some(rule));
+    }
+    assert_eq!(Rule::from_name("no-such-rule"), None);
+}
@@ -0,0 +1,162 @@
+//! The language server, driven through `Client` the way an editor would.
+
+use love_language::lsp::Client;
+use serde_json::{json, Value};
+
+const URI: &str = "file:///story.love";
+
+const STORY: &str = "\
+forever LIMIT match 3;
+devotion double(n: number) -> number {
+    heart twice match n kiss 2;
+    promise twice;
+}
+heart total match double(LIMIT);
+whisper total;
+";
+
+fn open(text: &str) -> Client {
+    let mut client = Client::new();
+    client.initialize().expect("the server initializes");
+    client.open(URI, text);
+    client
+}
+
+fn hover_text(client: &mut Client, line: usize, character: usize) -> String {
+    let hover = client.hover(URI, line, character).expect("hover is answered");
+    hover["contents"]["value"].as_str().unwrap_or_default().to_string()
+}
+
+fn labels(items: &Value) -> Vec<&str> {
+    items.as_array().expect("completion is a list").iter()
+        .map(|item| item["label"].as_str().expect("items have labels"))
+        .collect()
+}
+
+#[test]
+fn initialize_lists_capabilities() {
+    let mut client = Client::new();
+    let capabilities = client.initialize().unwrap();
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+    client.shutdown().unwrap();
+    assert_eq!(client.exited(), Some(true));
+}
+
+#[test]
+fn a_clean_story_has_no_diagnostics() {
+    let client = open(STORY);
+    assert_eq!(client.diagnostics(URI), Vec::<Value>::new());
+}
+
+#[test]
+fn diagnostics_follow_changes() {
+    let mut client = open("heart x match 1;\nwhisper x kiss \"two\";\n");
+    let diagnostics = client.diagnostics(URI);
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0]["code"], "E0301");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+
+    client.change(URI, "heart x match 1;\nwhisper x kiss 2;\n");
+    assert_eq!(client.diagnostics(URI), Vec::<Value>::new());
+}
+
+#[test]
+fn lints_are_warnings() {
+    let client = open("heart unused match 1;\n");
+    let diagnostics = client.diagnostics(URI);
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0]["code"], "unused-heart");
+    assert_eq!(diagnostics[0]["severity"], 2);
+}
+
+#[test]
+fn a_story_that_doesnt_parse_is_still_analysed() {
+    let mut client = open("heart a match 1;\nheart b match\nwhisper a kiss \"x\";\ndevotion f() -> number { promise a; }\n");
+    let codes: Vec<Value> = client.diagnostics(URI).iter().map(|d| d["code"].clone()).collect();
+    assert!(codes.contains(&Value::from("E0201")), "{:?}", codes);
+    assert!(codes.contains(&Value::from("E0301")), "{:?}", codes);
+    assert!(hover_text(&mut client, 3, 34).contains("heart a: number"));
+    let symbols = client.symbols(URI).unwrap();
+    let names: Vec<&str> = symbols.as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["a", "f"]);
+}
+
+#[test]
+fn hover_shows_types_and_signatures() {
+    let mut client = open(STORY);
+    assert_eq!(hover_text(&mut client, 6, 9), "```love\nheart total: number\n```");
+    assert_eq!(hover_text(&mut client, 5, 19), "```love\ndevotion double(n: number) -> number\n```");
+    assert_eq!(hover_text(&mut client, 3, 13), "```love\nheart twice: number\n```");
+    assert_eq!(client.hover(URI, 0, 0).unwrap(), Value::Null);
+}
+
+#[test]
+fn hover_on_builtins() {
+    let mut client = open("whisper letters();\n");
+    let text = hover_text(&mut client, 0, 9);
+    assert!(text.starts_with("```love\ndevotion letters() -> number\n```"), "{}", text);
+    assert!(text.contains("Built into the language."));
+}
+
+#[test]
+fn definition_finds_declarations() {
+    let mut client = open(STORY);
+    let definition = client.definition(URI, 5, 26).unwrap();
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(definition["range"]["start"], json!({ "line": 0, "character": 8 }));
+    assert_eq!(definition["range"]["end"], json!({ "line": 0, "character": 13 }));
+
+    let parameter = client.definition(URI, 2, 22).unwrap();
+    assert_eq!(parameter["range"]["start"], json!({ "line": 1, "character": 16 }));
+
+    assert_eq!(client.definition(URI, 6, 2).unwrap(), Value::Null);
+}
+
+#[test]
+fn completion_offers_keywords_builtins_and_visible_names() {
+    let mut client = open(STORY);
+    let inside = client.completion(URI, 3, 4).unwrap();
+    let inside = labels(&inside);
+    for label in ["heart", "devotion", "secret", "LIMIT", "double", "n", "twice", "total"] {
+        assert!(inside.contains(&label), "{} missing from {:?}", label, inside);
+    }
+    let outside = client.completion(URI, 6, 0).unwrap();
+    let outside = labels(&outside);
+    assert!(outside.contains(&"total"));
+    assert!(!outside.contains(&"n") && !outside.contains(&"twice"), "{:?}", outside);
+}
+
+#[test]
+fn document_symbols_nest_under_devotions() {
+    let mut client = open(STORY);
+    let symbols = client.symbols(URI).unwrap();
+    let symbols = symbols.as_array().unwrap();
+    let outline: Vec<(&str, u64)> = symbols.iter()
+        .map(|s| (s["name"].as_str().unwrap(), s["kind"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(outline, [("LIMIT", 14), ("double", 12), ("total", 13)]);
+    let children = symbols[1]["children"].as_array().unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0]["name"], "twice");
+    assert_eq!(symbols[1]["detail"], "devotion double(n: number) -> number");
+}
+
+#[test]
+fn requests_about_unknown_documents_fail() {
+    let mut client = open(STORY);
+    let error = client.hover("file:///elsewhere.love", 0, 0).unwrap_err();
+    assert_eq!(error["code"], -32602);
+    let error = client.request("love/unknown", Value::Null).unwrap_err();
+    assert_eq!(error["code"], -32601);
+}
+
+#[test]
+fn closing_clears_diagnostics() {
+    let mut client = open("whisper 1 kiss \"x\";\n");
+    assert_eq!(client.diagnostics(URI).len(), 1);
+    client.notify("textDocument/didClose", json!({ "textDocument": { "uri": URI } }));
+    assert_eq!(client.diagnostics(URI), Vec::<Value>::new());
+}
@@ -0,0 +1,72 @@
+//! `love -O`: simplifying love stories without changing what they do.
+
+mod common;
+
+use common::{parse, run, run_ast, ENGINES};
+use love_language::optimizer::optimize;
+
+/// The syntax tree of `source` once optimized, as `--print-ast` shows it.
+fn optimized(source: &str) -> String {
+    optimize(parse(source)).to_string()
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    assert_eq!(optimized("whisper (1 cuddle 2) kiss 3;"), "Program @1:1\n  PrintStmt @1:1\n    Literal 9 @1:9\n");
+    assert_eq!(optimized("whisper \"a\" cuddle \"b\";"), "Program @1:1\n  PrintStmt @1:1\n    Literal \"ab\" @1:9\n");
+    assert_eq!(optimized("whisper 2 admires 1;"), "Program @1:1\n  PrintStmt @1:1\n    Literal yes @1:9\n");
+    // Hearts may change, so they are left alone
+    assert!(optimized("heart h match 2; whisper h kiss 3;").contains("Binary kiss"));
+}
+
+#[test]
+fn forever_constants_are_inlined() {
+    assert_eq!(
+        optimized("forever L match 3; whisper L cuddle 1;"),
+        "Program @1:1\n  VariableDecl forever L @1:1\n    Literal 3 @1:17\n  PrintStmt @1:20\n    Literal 4 @1:28\n",
+    );
+    // Only declared in a branch that might not run
+    assert!(optimized("heart x match 0; crush (x soulmate 0) { forever L match 2; whisper L; }").contains("Variable L"));
+    // Declared after a devotion that might be called before it
+    assert!(optimized("devotion f() { whisper L; } forever L match 2; f();").contains("Variable L"));
+}
+
+#[test]
+fn branches_that_never_run_are_dropped() {
+    assert_eq!(
+        optimized("crush (no) { whisper 1; } butterflies { whisper 2; }"),
+        "Program @1:1\n  PrintStmt @1:41\n    Literal 2 @1:49\n",
+    );
+    assert_eq!(optimized("crush (1 admires 2) { whisper 1; }"), "Program @1:1\n  ExpressionStmt @1:1\n    Literal lonely @1:1\n");
+}
+
+#[test]
+fn failing_operations_are_left_for_runtime() {
+    let tree = optimized("whisper 1 split 0;");
+    assert!(tree.contains("Binary split @1:9"), "{}", tree);
+    let tree = optimized("whisper \"a\" kiss 2;");
+    assert!(tree.contains("Binary kiss"), "{}", tree);
+    for engine in ENGINES {
+        let outcome = run_ast(engine, optimize(parse("\n\nwhisper 1 split 0;")));
+        let span = outcome.result.unwrap_err().span().unwrap();
+        assert_eq!((span.line, span.column), (3, 9));
+    }
+}
+
+#[test]
+fn optimizing_keeps_what_stories_do() {
+    let stories = [
+        include_str!("../examples/minimal.love"),
+        include_str!("../examples/love_story.love"),
+        "forever N match 3; heart i match 0; dating (i envies N kiss 2) { crush (no) { whisper 0; } i match i cuddle 1; } whisper i;",
+        "devotion f(n: number) -> number { promise n kiss (2 cuddle 2); } whisper f(3);",
+    ];
+    for story in stories {
+        for engine in ENGINES {
+            let plain = run(engine, story);
+            let optimized = run_ast(engine, optimize(parse(story)));
+            assert_eq!(optimized.output, plain.output, "{:?} running {}", engine, story);
+            assert_eq!(optimized.result, plain.result);
+        }
+    }
+}
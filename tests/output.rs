@@ -0,0 +1,62 @@
+//! Where `whisper` output goes when love stories are embedded, and how
+//! whispered values look.
+
+mod common;
+
+use std::io::Write;
+
+use common::{parse, run, ENGINES};
+use love_language::interpreter::{Interpreter, SharedBuffer};
+use love_language::runner::{Runner, Source};
+use love_language::vm::Vm;
+
+#[test]
+fn whispers_are_captured() {
+    for engine in ENGINES {
+        let outcome = run(engine, "whisper \"hello\"; whisper 1 cuddle 2; whisper yes; devotion quiet() { } whisper quiet();");
+        assert_eq!(outcome.output, "hello\n3\nyes\nlonely\n", "{:?}", engine);
+    }
+}
+
+#[test]
+fn whispered_values_read_like_people_write_them() {
+    for engine in ENGINES {
+        let outcome = run(engine, "whisper 5 split 2; whisper 0 kiss -1; whisper no; devotion hug(a: number, b: text) { } whisper hug;");
+        assert_eq!(outcome.output, "2.5\n0\nno\n<devotion hug(a, b)>\n", "{:?}", engine);
+    }
+}
+
+#[test]
+fn set_output_hands_back_the_previous_sink() {
+    let first = SharedBuffer::new();
+    let second = SharedBuffer::new();
+    let mut interpreter = Interpreter::with_output(Box::new(first.clone()));
+    interpreter.interpret(parse("whisper 1;")).unwrap();
+    let mut previous = interpreter.set_output(Box::new(second.clone()));
+    interpreter.interpret(parse("whisper 2;")).unwrap();
+    previous.write_all(b"3\n").unwrap();
+    assert_eq!(first.contents(), "1\n3\n");
+    assert_eq!(second.contents(), "2\n");
+
+    let first = SharedBuffer::new();
+    let second = SharedBuffer::new();
+    let mut vm = Vm::with_output(Box::new(first.clone()));
+    vm.interpret(parse("whisper 1;")).unwrap();
+    vm.set_output(Box::new(second.clone()));
+    vm.interpret(parse("whisper 2;")).unwrap();
+    assert_eq!(first.contents(), "1\n");
+    assert_eq!(second.contents(), "2\n");
+}
+
+#[test]
+fn runner_sends_only_whispers_to_its_output_when_plain() {
+    let buffer = SharedBuffer::new();
+    let mut runner = Runner::with_output(Box::new(buffer.clone()));
+    runner.set_plain(true);
+    runner.run(&Source::Inline("whisper \"first date\";".to_string())).unwrap();
+    assert_eq!(buffer.contents(), "first date\n");
+
+    buffer.clear();
+    assert!(runner.run(&Source::Inline("whisper 1 split 0;".to_string())).is_err());
+    assert_eq!(buffer.contents(), "");
+}
@@ -0,0 +1,204 @@
+//! The tree-walking interpreter and the VM run the same love story the same
+//! way: same output, same error code.
+
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use common::{run_with, Outcome, Setup};
+use love_language::interpreter::Limits;
+use love_language::runner::Engine;
+
+/// Runs `source` on both engines, checks they agree, and returns what they
+/// did.
+fn both(source: &str, setup: &Setup) -> Outcome {
+    let tree = run_with(Engine::TreeWalker, source, setup);
+    let vm = run_with(Engine::Vm, source, setup);
+    assert_eq!(tree.output, vm.output, "engines whisper differently for:\n{}", source);
+    assert_eq!(tree.code(), vm.code(), "engines fail differently for:\n{}\n{:?}\n{:?}", source, tree.result, vm.result);
+    assert_eq!(
+        tree.result.as_ref().err().map(|e| e.exit_code()),
+        vm.result.as_ref().err().map(|e| e.exit_code()),
+    );
+    tree
+}
+
+fn agree(source: &str) -> Outcome {
+    both(source, &Setup::default())
+}
+
+#[test]
+fn recursion() {
+    let outcome = agree(
+        "devotion fib(n: number) -> number {
+            crush (n envies 2) { promise n; }
+            promise fib(n breakup 1) cuddle fib(n breakup 2);
+        }
+        devotion countdown(n: number) {
+            crush (n admires 0) {
+                whisper n;
+                countdown(n breakup 1);
+            }
+        }
+        whisper fib(15);
+        countdown(3);",
+    );
+    assert_eq!(outcome.code(), None);
+    assert_eq!(outcome.output, "610\n3\n2\n1\n");
+}
+
+#[test]
+fn devotions_see_globals_as_they_are_when_called() {
+    let outcome = agree(
+        "devotion show() { whisper greeting cuddle \", \" cuddle name; }
+        heart greeting match \"hello\";
+        heart name match \"romeo\";
+        show();
+        name match \"juliet\";
+        show();
+        devotion bump() { count match count cuddle 1; }
+        heart count match 0;
+        bump(); bump();
+        whisper count;",
+    );
+    assert_eq!(outcome.code(), None);
+    assert_eq!(outcome.output, "hello, romeo\nhello, juliet\n2\n");
+}
+
+#[test]
+fn loops_blocks_and_branches() {
+    let outcome = agree(
+        "heart total match 0;
+        heart i match 0;
+        dating (i envies 5) {
+            crush (i soulmate 2) { total match total cuddle 100; } butterflies { total match total cuddle i; }
+            i match i cuddle 1;
+        }
+        { heart total match \"shadow\"; whisper total; }
+        whisper total;",
+    );
+    assert_eq!(outcome.output, "shadow\n108\n");
+}
+
+#[test]
+fn block_scopes_in_devotions() {
+    let outcome = agree(
+        "devotion f(a: number) -> number {
+            heart x match a;
+            { heart y match x cuddle 1; { heart z match y kiss 2; x match z; } }
+            { heart w match 10; x match x cuddle w; }
+            heart v match x;
+            promise v;
+        }
+        whisper f(1);
+        { heart a match \"first\"; whisper a; }
+        { heart b match \"second\"; { whisper b; } whisper b; }",
+    );
+    assert_eq!(outcome.code(), None);
+    assert_eq!(outcome.output, "14\nfirst\nsecond\nsecond\n");
+}
+
+#[test]
+fn goodbye_ends_the_story() {
+    let outcome = agree(
+        "devotion leave() { goodbye(7); }
+        whisper \"before\";
+        leave();
+        whisper \"after\";",
+    );
+    assert_eq!(outcome.output, "before\n");
+    assert_eq!(outcome.code(), Some("E0414"));
+    assert_eq!(outcome.result.unwrap_err().exit_code(), 7);
+}
+
+#[test]
+fn runtime_errors() {
+    let cases = [
+        ("whisper \"a\"; whisper 1 split 0;", "E0406", "a\n"),
+        ("devotion f(a: number) { promise a; } whisper f(1, 2);", "E0405", ""),
+        ("heart x match 1; whisper x();", "E0404", ""),
+        ("whisper \"love\" kiss 2;", "E0301", ""),
+        ("devotion f(n: number) -> number { promise n split 0; } whisper f(1);", "E0406", ""),
+        ("goodbye(300);", "E0401", ""),
+    ];
+    for (source, code, output) in cases {
+        let outcome = agree(source);
+        assert_eq!(outcome.code(), Some(code), "{}", source);
+        assert_eq!(outcome.output, output, "{}", source);
+    }
+}
+
+#[test]
+fn call_depth_limit() {
+    let setup = Setup { limits: Limits { max_call_depth: 20, ..Limits::default() }, ..Setup::default() };
+    let source = "devotion down(n: number) -> number {
+        crush (n soulmate 0) { promise 0; }
+        promise down(n breakup 1);
+    }";
+    let shallow = both(&format!("{}\nwhisper down(10);", source), &setup);
+    assert_eq!(shallow.output, "0\n");
+    let deep = both(&format!("{}\nwhisper down(50);", source), &setup);
+    assert_eq!(deep.output, "");
+    assert_eq!(deep.code(), Some("E0415"));
+
+    // The default limit, on a stack as roomy as the one `love` runs on
+    let default_depth = thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || {
+            let setup = Setup::default();
+            assert_eq!(setup.limits, Limits::default());
+            let within = both(&format!("{}\nwhisper down(199);", source), &setup);
+            assert_eq!(within.output, "0\n");
+            let beyond = both(&format!("{}\nwhisper down(500);", source), &setup);
+            assert_eq!(beyond.code(), Some("E0415"));
+        })
+        .unwrap();
+    default_depth.join().unwrap();
+}
+
+#[test]
+fn step_limit() {
+    let setup = Setup { limits: Limits { max_steps: Some(1_000), ..Limits::default() }, ..Setup::default() };
+    let outcome = both("heart i match 0; dating (yes) { i match i cuddle 1; }", &setup);
+    assert_eq!(outcome.code(), Some("E0410"));
+}
+
+#[test]
+fn time_limit() {
+    let setup = Setup { limits: Limits { timeout: Some(Duration::from_millis(50)), ..Limits::default() }, ..Setup::default() };
+    let outcome = both("dating (yes) { }", &setup);
+    assert_eq!(outcome.code(), Some("E0411"));
+}
+
+#[test]
+fn memory_limit() {
+    let setup = Setup { limits: Limits { max_memory: Some(1_000), ..Limits::default() }, ..Setup::default() };
+    let outcome = both(
+        "heart s match \"love\";
+        dating (yes) { s match s cuddle s; }",
+        &setup,
+    );
+    assert_eq!(outcome.code(), Some("E0413"));
+
+    // Leaving a block gives back what its names held
+    let letter = "x".repeat(1_000);
+    let blocks = format!(
+        "{{ heart a match \"{0}\"; }} {{ heart b match \"{0}\"; }} heart c match \"{0}\"; whisper \"done\";",
+        letter,
+    );
+    for max_memory in [1_300, 1_500, 2_000] {
+        let setup = Setup { limits: Limits { max_memory: Some(max_memory), ..Limits::default() }, ..Setup::default() };
+        let outcome = both(&blocks, &setup);
+        assert_eq!(outcome.code(), None, "max_memory {}", max_memory);
+        assert_eq!(outcome.output, "done\n");
+    }
+
+    // Builtins come free, so a tight budget is all the story's
+    let setup = Setup { limits: Limits { max_memory: Some(300), ..Limits::default() }, ..Setup::default() };
+    let outcome = both(&format!("heart s match \"{}\"; whisper \"kept\";", "x".repeat(100)), &setup);
+    assert_eq!(outcome.code(), None);
+    assert_eq!(outcome.output, "kept\n");
+    let outcome = both(&format!("heart s match \"{}\";", "x".repeat(300)), &setup);
+    assert_eq!(outcome.code(), Some("E0413"));
+}
@@ -0,0 +1,61 @@
+//! Parsing, and recovering from broken statements.
+
+use love_language::error::LoveError;
+use love_language::lexer::Lexer;
+use love_language::parser::ast::Ast;
+use love_language::parser::Parser;
+
+fn parse_recovering(source: &str) -> (Ast, Vec<LoveError>) {
+    let tokens = Lexer::new(source).tokenize().expect("test source lexes");
+    Parser::new(tokens).parse_recovering()
+}
+
+fn statement_count(ast: &Ast) -> usize {
+    match ast {
+        Ast::Program { statements, .. } => statements.len(),
+        _ => panic!("the parser returns a program"),
+    }
+}
+
+fn error_lines(errors: &[LoveError]) -> Vec<usize> {
+    errors.iter().map(|e| e.span().expect("parser errors have spans").line).collect()
+}
+
+#[test]
+fn reports_missing_semicolons_on_consecutive_lines() {
+    let (_, errors) = parse_recovering("heart a match 1\nheart b match 2\nheart c match 3\nwhisper a;\n");
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(errors.iter().all(|e| e.code() == "E0201"));
+    assert_eq!(error_lines(&errors), [1, 2, 3]);
+}
+
+#[test]
+fn keeps_statements_after_a_broken_one() {
+    let (ast, errors) = parse_recovering("heart a match;\nheart b match 2;\nwhisper b;\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(statement_count(&ast), 2);
+}
+
+#[test]
+fn recovers_inside_blocks() {
+    let source = "devotion f() {\n    heart a match\n    heart b match 2;\n    promise b\n}\nwhisper f();\n";
+    let (ast, errors) = parse_recovering(source);
+    assert_eq!(error_lines(&errors), [3, 4]);
+    assert_eq!(statement_count(&ast), 2);
+}
+
+#[test]
+fn parse_joins_several_errors() {
+    let tokens = Lexer::new("heart a match 1\nheart b match 2\n").tokenize().unwrap();
+    match Parser::new(tokens).parse() {
+        Err(LoveError::Multiple { errors }) => assert_eq!(errors.len(), 2),
+        other => panic!("expected two errors, got {:?}", other),
+    }
+}
+
+#[test]
+fn parses_a_clean_story_without_errors() {
+    let (ast, errors) = parse_recovering("forever LIMIT match 3;\ndevotion f(n: number) -> number { promise n; }\nwhisper f(LIMIT);\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(statement_count(&ast), 3);
+}
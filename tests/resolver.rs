@@ -0,0 +1,84 @@
+//! Binding names before a love story runs.
+
+mod common;
+
+use common::{parse, run, ENGINES};
+use love_language::error::LoveError;
+use love_language::resolver::Resolver;
+
+fn resolve(source: &str) -> Result<(), LoveError> {
+    Resolver::new().resolve(&mut parse(source))
+}
+
+fn codes(source: &str) -> Vec<&'static str> {
+    match resolve(source) {
+        Ok(()) => Vec::new(),
+        Err(e) => e.errors().into_iter().map(LoveError::code).collect(),
+    }
+}
+
+#[test]
+fn devotions_in_blocks_are_rejected() {
+    let source = "{
+        devotion r(n: number) -> number {
+            crush (n soulmate 0) { promise 0; }
+            promise r(n breakup 1);
+        }
+        whisper r(3);
+    }";
+    assert_eq!(codes(source), ["E0503"]);
+    for engine in ENGINES {
+        let outcome = run(engine, source);
+        assert_eq!(outcome.code(), Some("E0503"), "{:?}", engine);
+        assert_eq!(outcome.output, "");
+    }
+}
+
+#[test]
+fn devotions_in_devotions_are_rejected() {
+    let source = "devotion outer() {
+        devotion inner() { whisper 1; }
+        inner();
+    }";
+    match resolve(source) {
+        Err(LoveError::NestedDevotion { name, span }) => {
+            assert_eq!(name, "inner");
+            assert_eq!(span.line, 2);
+        }
+        other => panic!("expected a nested devotion error, got {:?}", other),
+    }
+}
+
+#[test]
+fn devotions_under_top_level_crush_are_still_globals() {
+    let source = "crush (yes) { devotion f() -> number { promise 1; } } whisper f();";
+    assert_eq!(codes(source), Vec::<&str>::new());
+    for engine in ENGINES {
+        assert_eq!(run(engine, source).output, "1\n", "{:?}", engine);
+    }
+}
+
+#[test]
+fn reports_use_before_declaration_and_duplicates() {
+    assert_eq!(codes("{ whisper x; heart x match 1; }"), ["E0501"]);
+    assert_eq!(codes("{ heart x match 1; heart x match 2; }"), ["E0502"]);
+    assert_eq!(codes("devotion f(a: number, a: number) { }"), ["E0502"]);
+    assert_eq!(codes("{ heart x match 1; heart x match 2; } { whisper y; heart y match 1; }"), ["E0502", "E0501"]);
+}
+
+#[test]
+fn duplicate_parameters_point_at_the_parameter() {
+    match resolve("devotion f(a: number, a: number) { }") {
+        Err(LoveError::DuplicateDeclaration { name, span, previous }) => {
+            assert_eq!(name, "a");
+            assert_eq!((span.start, span.end), (22, 23));
+            assert_eq!((previous.start, previous.end), (11, 12));
+        }
+        other => panic!("expected a duplicate declaration, got {:?}", other),
+    }
+}
+
+#[test]
+fn devotions_may_call_globals_declared_later() {
+    assert_eq!(codes("devotion f() { promise g(); } devotion g() { promise 1; } whisper f();"), Vec::<&str>::new());
+}